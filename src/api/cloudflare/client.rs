@@ -4,8 +4,12 @@ use reqwest;
 
 use super::models::{
     CloudflareResponse, EmailRoute, EmailRouteAction, EmailRouteCreate, EmailRouteMatcher,
+    EmailRoutingSettings, managed_rule_name,
 };
 
+/// 每页获取的路由规则数量
+const ROUTES_PER_PAGE: u32 = 50;
+
 /// Cloudflare API客户端，用于操作Email Routing服务
 pub struct CloudflareClient {
     client: reqwest::Client,
@@ -20,8 +24,8 @@ impl CloudflareClient {
     pub fn new(config: &Config) -> Self {
         // 根据配置的认证类型选择认证方式
         let client = match config.cloudflare.auth_type.as_str() {
-            "api_token" => Self::create_token_client(&config).expect("无法创建API Token客户端"),
-            _ => Self::create_key_client(&config).expect("无法创建API Key客户端"),
+            "api_token" => Self::create_token_client(config).expect("无法创建API Token客户端"),
+            _ => Self::create_key_client(config).expect("无法创建API Key客户端"),
         };
 
         Self {
@@ -53,10 +57,10 @@ impl CloudflareClient {
             reqwest::header::HeaderValue::from_static("application/json"),
         );
 
-        Ok(reqwest::Client::builder()
+        reqwest::Client::builder()
             .default_headers(headers)
             .build()
-            .context("无法创建HTTP客户端")?)
+            .context("无法创建HTTP客户端")
     }
 
     /// 使用API Key创建客户端
@@ -86,10 +90,56 @@ impl CloudflareClient {
             reqwest::header::HeaderValue::from_static("application/json"),
         );
 
-        Ok(reqwest::Client::builder()
+        reqwest::Client::builder()
             .default_headers(headers)
             .build()
-            .context("无法创建HTTP客户端")?)
+            .context("无法创建HTTP客户端")
+    }
+
+    /// 区域邮件路由接口地址，`path` 为 `/email/routing` 之后的部分
+    fn routing_url(&self, path: &str) -> String {
+        format!(
+            "https://api.cloudflare.com/client/v4/zones/{}/email/routing{}",
+            self.zone_id, path
+        )
+    }
+
+    /// 路由规则接口地址
+    fn rules_url(&self) -> String {
+        self.routing_url("/rules")
+    }
+
+    /// 检查HTTP状态并解析Cloudflare响应
+    async fn parse_response<T: serde::de::DeserializeOwned>(
+        response: reqwest::Response,
+    ) -> Result<CloudflareResponse<T>> {
+        // 获取响应状态和文本以便更好地诊断
+        let status = response.status();
+        let body = response.text().await.context("读取响应内容失败")?;
+
+        if !status.is_success() {
+            return Err(anyhow!(
+                "Cloudflare API错误: 状态码 {}，响应内容: {}",
+                status,
+                body
+            ));
+        }
+
+        let cf_response = serde_json::from_str::<CloudflareResponse<T>>(&body)
+            .map_err(|e| anyhow!("无法解析Cloudflare响应: {}，原始响应: {}", e, body))?;
+
+        if !cf_response.success {
+            let error_msg = cf_response
+                .errors
+                .into_iter()
+                .map(|e| format!("{}: {}", e.code, e.message))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            return Err(anyhow!("Cloudflare API错误: {}", error_msg));
+        }
+
+        Ok(cf_response)
     }
 
     /// 创建邮箱路由规则
@@ -107,227 +157,162 @@ impl CloudflareClient {
                 value: vec![self.target_email.clone()],
             }],
             enabled: true,
-            name: Some(format!("自动创建的转发规则: {}", email_alias)),
+            name: Some(managed_rule_name(email_alias)),
         };
 
-        let url = format!(
-            "https://api.cloudflare.com/client/v4/zones/{}/email/routing/rules",
-            self.zone_id
-        );
+        self.create_route(&email_route).await
+    }
 
+    /// 按给定内容创建路由规则
+    pub async fn create_route(&self, rule: &EmailRouteCreate) -> Result<()> {
         let response = self
             .client
-            .post(&url)
-            .json(&email_route)
+            .post(self.rules_url())
+            .json(rule)
             .send()
             .await
             .context("发送请求创建邮箱路由失败")?;
 
-        // 获取响应状态和文本以便更好地诊断
-        let status = response.status();
-        let body = response.text().await.context("读取响应内容失败")?;
-
-        if !status.is_success() {
-            return Err(anyhow!(
-                "Cloudflare API错误: 状态码 {}，响应内容: {}",
-                status,
-                body
-            ));
-        }
-
-        // 尝试解析JSON，使用EmailRoute结构作为响应类型
-        match serde_json::from_str::<CloudflareResponse<EmailRoute>>(&body) {
-            Ok(cf_response) => {
-                if !cf_response.success {
-                    let error_msg = cf_response
-                        .errors
-                        .into_iter()
-                        .map(|e| format!("{}: {}", e.code, e.message))
-                        .collect::<Vec<_>>()
-                        .join(", ");
-
-                    return Err(anyhow!("Cloudflare API错误: {}", error_msg));
-                }
-
-                // 创建成功
-                Ok(())
-            }
-            Err(e) => Err(anyhow!("无法解析Cloudflare响应: {}，原始响应: {}", e, body)),
-        }
+        Self::parse_response::<EmailRoute>(response).await?;
+        Ok(())
     }
 
-    /// 获取已配置的邮箱别名列表
-    pub async fn list_email_routes(&self) -> Result<Vec<String>> {
-        let url = format!(
-            "https://api.cloudflare.com/client/v4/zones/{}/email/routing/rules",
-            self.zone_id
-        );
-
+    /// 用给定内容替换已有的路由规则
+    pub async fn update_route(&self, route_id: &str, rule: &EmailRouteCreate) -> Result<()> {
         let response = self
             .client
-            .get(&url)
+            .put(format!("{}/{}", self.rules_url(), route_id))
+            .json(rule)
             .send()
             .await
-            .context("发送请求获取邮箱路由列表失败")?;
+            .context("发送请求更新邮箱路由失败")?;
 
-        // 获取响应状态和文本以便更好地诊断
-        let status = response.status();
-        let body = response.text().await.context("读取响应内容失败")?;
+        Self::parse_response::<EmailRoute>(response).await?;
+        Ok(())
+    }
 
-        if !status.is_success() {
-            return Err(anyhow!(
-                "Cloudflare API错误: 状态码 {}，响应内容: {}",
-                status,
-                body
-            ));
+    /// 获取全部路由规则（自动处理分页）
+    pub async fn list_routes(&self) -> Result<Vec<EmailRoute>> {
+        let mut routes = Vec::new();
+        let mut page = 1;
+
+        loop {
+            let response = self
+                .client
+                .get(self.rules_url())
+                .query(&[("page", page), ("per_page", ROUTES_PER_PAGE)])
+                .send()
+                .await
+                .context("发送请求获取邮箱路由列表失败")?;
+
+            let cf_response = Self::parse_response::<Vec<EmailRoute>>(response).await?;
+            routes.extend(cf_response.result.unwrap_or_default());
+
+            match cf_response.result_info {
+                Some(info) if info.page < info.total_pages => page = info.page + 1,
+                _ => break,
+            }
         }
 
-        // 尝试解析JSON，如果失败则返回原始文本
-        match serde_json::from_str::<CloudflareResponse<Vec<EmailRoute>>>(&body) {
-            Ok(cf_response) => {
-                if !cf_response.success {
-                    let error_msg = cf_response
-                        .errors
-                        .into_iter()
-                        .map(|e| format!("{}: {}", e.code, e.message))
-                        .collect::<Vec<_>>()
-                        .join(", ");
-
-                    return Err(anyhow!("Cloudflare API错误: {}", error_msg));
+        Ok(routes)
+    }
+
+    /// 获取已配置的邮箱别名列表
+    pub async fn list_email_routes(&self) -> Result<Vec<String>> {
+        let aliases = self
+            .list_routes()
+            .await?
+            .into_iter()
+            .filter_map(|route| {
+                // 使用路由ID和名称(虽然在正常流程中不输出这些调试信息)
+                if cfg!(debug_assertions) {
+                    println!("处理邮件路由: ID={}, 名称={}", route.id, route.name);
                 }
 
-                let aliases = cf_response
-                    .result
-                    .unwrap_or_default()
-                    .into_iter()
-                    .filter_map(|route| {
-                        // 使用路由ID和名称(虽然在正常流程中不输出这些调试信息)
-                        if cfg!(debug_assertions) {
-                            println!("处理邮件路由: ID={}, 名称={}", route.id, route.name);
-                        }
-
-                        for matcher in route.matchers {
-                            if matcher.matcher_type == "literal"
-                                && matcher.field.as_deref() == Some("to")
-                                && matcher.value.is_some()
-                            {
-                                return matcher.value;
-                            }
-                        }
-                        None
-                    })
-                    .collect();
-
-                Ok(aliases)
-            }
-            Err(e) => Err(anyhow!("无法解析Cloudflare响应: {}，原始响应: {}", e, body)),
-        }
+                route.address().map(|address| address.to_string())
+            })
+            .collect();
+
+        Ok(aliases)
     }
 
     /// 删除指定的邮箱别名路由
     ///
     /// 根据邮箱别名找到对应的路由ID并删除
     pub async fn delete_email_route(&self, email_alias: &str) -> Result<()> {
-        // 获取所有路由
-        let url = format!(
-            "https://api.cloudflare.com/client/v4/zones/{}/email/routing/rules",
-            self.zone_id
-        );
-
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .context("发送请求获取邮箱路由列表失败")?;
-
-        // 获取响应状态和文本以便更好地诊断
-        let status = response.status();
-        let body = response.text().await.context("读取响应内容失败")?;
-
-        if !status.is_success() {
-            return Err(anyhow!(
-                "Cloudflare API错误: 状态码 {}，响应内容: {}",
-                status,
-                body
-            ));
-        }
-
-        // 解析响应以查找匹配的路由ID
-        let cf_response = serde_json::from_str::<CloudflareResponse<Vec<EmailRoute>>>(&body)
-            .context("无法解析Cloudflare响应")?;
-
-        if !cf_response.success {
-            let error_msg = cf_response
-                .errors
-                .into_iter()
-                .map(|e| format!("{}: {}", e.code, e.message))
-                .collect::<Vec<_>>()
-                .join(", ");
+        // 查找匹配的路由ID
+        let route_id = self
+            .list_routes()
+            .await?
+            .into_iter()
+            .find(|route| route.address() == Some(email_alias))
+            .map(|route| route.id)
+            .ok_or_else(|| anyhow!("未找到匹配的邮箱别名: {}", email_alias))?;
 
-            return Err(anyhow!("Cloudflare API错误: {}", error_msg));
-        }
+        self.delete_route(&route_id).await
+    }
 
-        // 查找匹配的路由ID
-        let route_id = cf_response
-            .result
-            .unwrap_or_default()
+    /// 禁用邮箱别名的路由规则，保留规则以便之后重新启用
+    pub async fn disable_email_route(&self, email_alias: &str) -> Result<()> {
+        let route = self
+            .list_routes()
+            .await?
             .into_iter()
-            .find_map(|route| {
-                for matcher in route.matchers {
-                    if matcher.matcher_type == "literal"
-                        && matcher.field.as_deref() == Some("to")
-                        && matcher.value.as_deref() == Some(email_alias)
-                    {
-                        return Some(route.id);
-                    }
-                }
-                None
-            })
+            .find(|route| route.address() == Some(email_alias))
             .ok_or_else(|| anyhow!("未找到匹配的邮箱别名: {}", email_alias))?;
 
-        // 删除找到的路由
-        let delete_url = format!(
-            "https://api.cloudflare.com/client/v4/zones/{}/email/routing/rules/{}",
-            self.zone_id, route_id
-        );
+        let rule = EmailRouteCreate {
+            matchers: route.matchers,
+            actions: route.actions,
+            enabled: false,
+            name: Some(route.name),
+        };
+        self.update_route(&route.id, &rule).await
+    }
 
+    /// 按ID删除路由规则
+    pub async fn delete_route(&self, route_id: &str) -> Result<()> {
         let delete_response = self
             .client
-            .delete(&delete_url)
+            .delete(format!("{}/{}", self.rules_url(), route_id))
             .send()
             .await
             .context("发送请求删除邮箱路由失败")?;
 
-        // 获取响应状态和文本以便更好地诊断
-        let delete_status = delete_response.status();
-        let delete_body = delete_response.text().await.context("读取响应内容失败")?;
+        Self::parse_response::<serde_json::Value>(delete_response)
+            .await
+            .context("Cloudflare API删除错误")?;
 
-        if !delete_status.is_success() {
-            return Err(anyhow!(
-                "Cloudflare API错误: 状态码 {}，响应内容: {}",
-                delete_status,
-                delete_body
-            ));
-        }
+        // 删除成功
+        Ok(())
+    }
 
-        // 解析删除响应
-        let delete_cf_response =
-            serde_json::from_str::<CloudflareResponse<serde_json::Value>>(&delete_body)
-                .context("无法解析Cloudflare删除响应")?;
+    /// 获取区域的邮件路由设置
+    pub async fn get_routing_settings(&self) -> Result<EmailRoutingSettings> {
+        let response = self
+            .client
+            .get(self.routing_url(""))
+            .send()
+            .await
+            .context("发送请求获取邮件路由设置失败")?;
 
-        if !delete_cf_response.success {
-            let error_msg = delete_cf_response
-                .errors
-                .into_iter()
-                .map(|e| format!("{}: {}", e.code, e.message))
-                .collect::<Vec<_>>()
-                .join(", ");
+        Self::parse_response::<EmailRoutingSettings>(response)
+            .await?
+            .result
+            .ok_or_else(|| anyhow!("Cloudflare响应中缺少邮件路由设置"))
+    }
 
-            return Err(anyhow!("Cloudflare API删除错误: {}", error_msg));
-        }
+    /// 为域名或子域名启用邮件路由（添加所需的DNS记录）
+    pub async fn enable_routing_dns(&self, name: &str) -> Result<()> {
+        let response = self
+            .client
+            .post(self.routing_url("/dns"))
+            .json(&serde_json::json!({ "name": name }))
+            .send()
+            .await
+            .context("发送请求启用邮件路由失败")?;
 
-        // 删除成功
+        Self::parse_response::<serde_json::Value>(response).await?;
         Ok(())
     }
 }
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-// 邮件路由创建请求
+// 邮件路由创建请求（也用于更新）
 #[derive(Debug, Serialize)]
 pub struct EmailRouteCreate {
     #[serde(rename = "matchers")]
@@ -14,7 +14,7 @@ pub struct EmailRouteCreate {
 }
 
 // 匹配器定义
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmailRouteMatcher {
     #[serde(rename = "type")]
     pub matcher_type: String,
@@ -25,11 +25,12 @@ pub struct EmailRouteMatcher {
 }
 
 // 动作定义
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmailRouteAction {
     #[serde(rename = "type")]
     pub action_type: String,
-    #[serde(rename = "value")]
+    // drop 动作没有 value
+    #[serde(rename = "value", default)]
     pub value: Vec<String>,
 }
 
@@ -42,6 +43,17 @@ pub struct CloudflareResponse<T> {
     pub errors: Vec<CloudflareError>,
     #[serde(rename = "result")]
     pub result: Option<T>,
+    #[serde(rename = "result_info", default)]
+    pub result_info: Option<ResultInfo>,
+}
+
+// 分页信息
+#[derive(Debug, Deserialize)]
+pub struct ResultInfo {
+    #[serde(rename = "page", default)]
+    pub page: u32,
+    #[serde(rename = "total_pages", default)]
+    pub total_pages: u32,
 }
 
 // Cloudflare错误定义
@@ -54,14 +66,50 @@ pub struct CloudflareError {
 }
 
 // 邮件路由规则
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct EmailRoute {
     #[serde(rename = "id")]
-    #[allow(dead_code)]
     pub id: String,
     #[serde(rename = "matchers")]
     pub matchers: Vec<EmailRouteMatcher>,
+    #[serde(rename = "actions", default)]
+    pub actions: Vec<EmailRouteAction>,
+    #[serde(rename = "enabled", default)]
+    pub enabled: bool,
     #[serde(rename = "name", default)]
-    #[allow(dead_code)]
     pub name: String,
 }
+
+// 邮件路由设置
+#[derive(Debug, Deserialize)]
+pub struct EmailRoutingSettings {
+    #[serde(rename = "name", default)]
+    pub name: String,
+    #[serde(rename = "enabled", default)]
+    pub enabled: bool,
+    #[serde(rename = "status", default)]
+    pub status: String,
+}
+
+impl EmailRoute {
+    /// 获取规则匹配的收件地址（仅限 literal/to 匹配器）
+    pub fn address(&self) -> Option<&str> {
+        self.matchers
+            .iter()
+            .find(|m| m.matcher_type == "literal" && m.field.as_deref() == Some("to"))
+            .and_then(|m| m.value.as_deref())
+    }
+
+    /// 规则是否由 cfmail 创建和管理
+    pub fn is_managed(&self) -> bool {
+        self.name.starts_with(MANAGED_RULE_PREFIX)
+    }
+}
+
+/// cfmail 创建的规则名称前缀，用于识别受管理的规则
+pub const MANAGED_RULE_PREFIX: &str = "自动创建的转发规则: ";
+
+/// 生成受管理规则的名称
+pub fn managed_rule_name(email_alias: &str) -> String {
+    format!("{}{}", MANAGED_RULE_PREFIX, email_alias)
+}
@@ -0,0 +1,291 @@
+use crate::config::OAuth2Config;
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+
+/// 服务商预设的端点: (名称, 授权地址, 令牌地址, 设备授权地址, 权限范围)
+///
+/// Gmail 的 https://mail.google.com/ 权限不允许使用设备码流程，因此没有设备授权地址。
+const PROVIDERS: &[(&str, &str, &str, Option<&str>, &str)] = &[
+    (
+        "google",
+        "https://accounts.google.com/o/oauth2/v2/auth",
+        "https://oauth2.googleapis.com/token",
+        None,
+        "https://mail.google.com/",
+    ),
+    (
+        "microsoft",
+        "https://login.microsoftonline.com/common/oauth2/v2.0/authorize",
+        "https://login.microsoftonline.com/common/oauth2/v2.0/token",
+        Some("https://login.microsoftonline.com/common/oauth2/v2.0/devicecode"),
+        "https://outlook.office.com/IMAP.AccessAsUser.All offline_access",
+    ),
+];
+
+/// 令牌端点的响应
+#[derive(Debug, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    /// 刷新令牌，刷新时服务商可能不返回新的刷新令牌
+    pub refresh_token: Option<String>,
+    /// 访问令牌的有效期（秒）
+    pub expires_in: Option<i64>,
+}
+
+/// 设备授权端点的响应
+#[derive(Debug, Deserialize)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    /// 需要用户在验证页面输入的代码
+    pub user_code: String,
+    /// 验证页面（部分服务商使用 verification_url）
+    #[serde(alias = "verification_url")]
+    pub verification_uri: String,
+    /// 设备码的有效期（秒）
+    pub expires_in: u64,
+    /// 轮询间隔（秒）
+    #[serde(default = "default_device_interval")]
+    pub interval: u64,
+}
+
+fn default_device_interval() -> u64 {
+    5
+}
+
+/// 设备码流程的轮询结果
+pub enum DevicePoll {
+    /// 用户尚未完成授权
+    Pending,
+    /// 轮询过快，需要增加间隔
+    SlowDown,
+    /// 授权完成
+    Token(TokenResponse),
+}
+
+/// OAuth2 错误响应
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+/// OAuth2 客户端，用于获取和刷新IMAP访问令牌
+pub struct OAuthClient {
+    client: reqwest::Client,
+    client_id: String,
+    client_secret: Option<String>,
+    auth_url: Option<String>,
+    token_url: String,
+    device_auth_url: Option<String>,
+    scope: String,
+}
+
+impl OAuthClient {
+    /// 根据配置创建客户端，未填写的端点使用服务商预设
+    pub fn new(config: &OAuth2Config) -> Result<Self> {
+        let preset = match config.provider.as_deref() {
+            Some(name) => Some(
+                PROVIDERS
+                    .iter()
+                    .find(|(provider, ..)| provider.eq_ignore_ascii_case(name))
+                    .ok_or_else(|| anyhow!("不支持的OAuth2服务商: {}", name))?,
+            ),
+            None => None,
+        };
+
+        if config.client_id.is_empty() {
+            return Err(anyhow!("OAuth2 client_id不能为空"));
+        }
+
+        let token_url = config
+            .token_url
+            .clone()
+            .or_else(|| preset.map(|(_, _, token_url, ..)| token_url.to_string()))
+            .ok_or_else(|| anyhow!("缺少OAuth2 token_url配置"))?;
+        let scope = config
+            .scope
+            .clone()
+            .or_else(|| preset.map(|(.., scope)| scope.to_string()))
+            .ok_or_else(|| anyhow!("缺少OAuth2 scope配置"))?;
+
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .build()
+                .context("无法创建HTTP客户端")?,
+            client_id: config.client_id.clone(),
+            client_secret: config.client_secret.clone(),
+            auth_url: config
+                .auth_url
+                .clone()
+                .or_else(|| preset.map(|(_, auth_url, ..)| auth_url.to_string())),
+            token_url,
+            device_auth_url: config.device_auth_url.clone().or_else(|| {
+                preset.and_then(|(.., device_auth_url, _)| device_auth_url.map(String::from))
+            }),
+            scope,
+        })
+    }
+
+    /// 是否支持设备码流程
+    pub fn supports_device_flow(&self) -> bool {
+        self.device_auth_url.is_some()
+    }
+
+    /// 生成浏览器授权页面地址（授权码 + PKCE）
+    pub fn authorization_url(
+        &self,
+        redirect_uri: &str,
+        state: &str,
+        code_challenge: &str,
+    ) -> Result<String> {
+        let auth_url = self
+            .auth_url
+            .as_deref()
+            .ok_or_else(|| anyhow!("缺少OAuth2 auth_url配置"))?;
+
+        let url = reqwest::Url::parse_with_params(
+            auth_url,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", redirect_uri),
+                ("scope", self.scope.as_str()),
+                ("state", state),
+                ("code_challenge", code_challenge),
+                ("code_challenge_method", "S256"),
+                // 要求返回刷新令牌
+                ("access_type", "offline"),
+                ("prompt", "consent"),
+            ],
+        )
+        .context("无效的OAuth2 auth_url")?;
+
+        Ok(url.to_string())
+    }
+
+    /// 使用授权码换取令牌
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        redirect_uri: &str,
+        code_verifier: &str,
+    ) -> Result<TokenResponse> {
+        let response = self
+            .post_token(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", redirect_uri),
+                ("code_verifier", code_verifier),
+            ])
+            .await?;
+
+        Self::parse_token(response).await
+    }
+
+    /// 申请设备码
+    pub async fn request_device_code(&self) -> Result<DeviceAuthorization> {
+        let device_auth_url = self
+            .device_auth_url
+            .as_deref()
+            .ok_or_else(|| anyhow!("缺少OAuth2 device_auth_url配置"))?;
+
+        let response = self
+            .client
+            .post(device_auth_url)
+            .form(&[
+                ("client_id", self.client_id.as_str()),
+                ("scope", self.scope.as_str()),
+            ])
+            .send()
+            .await
+            .context("发送设备授权请求失败")?;
+
+        if !response.status().is_success() {
+            return Err(Self::parse_error(response).await);
+        }
+
+        response
+            .json::<DeviceAuthorization>()
+            .await
+            .context("解析设备授权响应失败")
+    }
+
+    /// 查询设备码授权结果
+    pub async fn poll_device_token(&self, device_code: &str) -> Result<DevicePoll> {
+        let response = self
+            .post_token(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                ("device_code", device_code),
+            ])
+            .await?;
+
+        if response.status().is_success() {
+            return Ok(DevicePoll::Token(Self::parse_token(response).await?));
+        }
+
+        let body = response.text().await.context("读取令牌响应失败")?;
+        match serde_json::from_str::<ErrorResponse>(&body) {
+            Ok(error) if error.error == "authorization_pending" => Ok(DevicePoll::Pending),
+            Ok(error) if error.error == "slow_down" => Ok(DevicePoll::SlowDown),
+            Ok(error) => Err(Self::describe_error(error)),
+            Err(_) => Err(anyhow!("获取令牌失败: {}", body)),
+        }
+    }
+
+    /// 使用刷新令牌获取新的访问令牌
+    pub async fn refresh(&self, refresh_token: &str) -> Result<TokenResponse> {
+        let response = self
+            .post_token(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+            ])
+            .await?;
+
+        Self::parse_token(response).await
+    }
+
+    /// 向令牌端点发送请求，自动附加客户端凭据
+    async fn post_token(&self, params: &[(&str, &str)]) -> Result<reqwest::Response> {
+        let mut form: Vec<(&str, &str)> = vec![("client_id", self.client_id.as_str())];
+        if let Some(secret) = self.client_secret.as_deref() {
+            form.push(("client_secret", secret));
+        }
+        form.extend_from_slice(params);
+
+        self.client
+            .post(&self.token_url)
+            .form(&form)
+            .send()
+            .await
+            .context("发送令牌请求失败")
+    }
+
+    async fn parse_token(response: reqwest::Response) -> Result<TokenResponse> {
+        if !response.status().is_success() {
+            return Err(Self::parse_error(response).await);
+        }
+
+        response
+            .json::<TokenResponse>()
+            .await
+            .context("解析令牌响应失败")
+    }
+
+    async fn parse_error(response: reqwest::Response) -> anyhow::Error {
+        let status = response.status();
+        match response.text().await {
+            Ok(body) => match serde_json::from_str::<ErrorResponse>(&body) {
+                Ok(error) => Self::describe_error(error),
+                Err(_) => anyhow!("OAuth2请求失败 ({}): {}", status, body),
+            },
+            Err(e) => anyhow!("OAuth2请求失败 ({}): {}", status, e),
+        }
+    }
+
+    fn describe_error(error: ErrorResponse) -> anyhow::Error {
+        match error.error_description {
+            Some(description) => anyhow!("OAuth2错误 {}: {}", error.error, description),
+            None => anyhow!("OAuth2错误 {}", error.error),
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use anyhow::{Context, Result};
+use crate::service::email::MAX_HMAC_TOKEN_LENGTH;
+use anyhow::{Context, Result, anyhow};
 use serde::Deserialize;
 use std::fs::{self, File, OpenOptions};
 use std::io::{Read, Write};
@@ -11,6 +12,8 @@ pub struct Config {
     pub email: EmailConfig,
     pub alias: AliasConfig,
     pub smtp: SmtpConfig,
+    #[serde(default)]
+    pub extract: ExtractConfig,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -31,6 +34,12 @@ fn default_auth_type() -> String {
 pub struct EmailConfig {
     pub domain: String,
     pub target_email: String,
+    /// 别名子域名模板，如 "team" 或 "{label}"，生成 alias@team.example.com
+    #[serde(default)]
+    pub subdomain_template: Option<String>,
+    /// 需要启用邮件路由的固定子域名
+    #[serde(default)]
+    pub subdomains: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -39,18 +48,159 @@ pub struct AliasConfig {
     pub custom_prefixes: Vec<String>,
     pub random_length: usize,
     pub random_charset: String,
+    /// 模板模式使用的别名模板，如 "{label}.{rand:4}"
+    #[serde(default)]
+    pub template: Option<String>,
+    /// 是否排除容易混淆的字符（0/o、1/l）
+    #[serde(default)]
+    pub exclude_ambiguous: bool,
+    /// words 字符集使用的单词数量
+    #[serde(default = "default_word_count")]
+    pub word_count: usize,
+    /// words 字符集的单词分隔符
+    #[serde(default = "default_word_separator")]
+    pub word_separator: String,
+    /// words 字符集末尾追加的数字位数（0 表示不追加）
+    #[serde(default = "default_word_digits")]
+    pub word_digits: usize,
+    /// 网站派生别名的后缀模式: none / random / hmac
+    #[serde(default = "default_site_suffix")]
+    pub site_suffix: String,
+    /// 网站派生别名的后缀长度（hmac 模式最多51位）
+    #[serde(default = "default_site_suffix_length")]
+    pub site_suffix_length: usize,
+    /// 派生别名使用的密钥（可被环境变量 CFMAIL_ALIAS_SECRET 覆盖）
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// 确定性模式生成的前缀长度（1-51）
+    #[serde(default = "default_deterministic_length")]
+    pub deterministic_length: usize,
+    /// 子地址模式默认使用的基础别名
+    #[serde(default)]
+    pub subaddress_base: Option<String>,
+    /// 子地址分隔符
+    #[serde(default = "default_subaddress_separator")]
+    pub subaddress_separator: String,
+}
+
+fn default_word_count() -> usize {
+    2
+}
+
+fn default_word_separator() -> String {
+    "-".to_string()
+}
+
+fn default_word_digits() -> usize {
+    2
+}
+
+fn default_site_suffix() -> String {
+    "none".to_string()
+}
+
+fn default_site_suffix_length() -> usize {
+    4
+}
+
+fn default_deterministic_length() -> usize {
+    10
+}
+
+fn default_subaddress_separator() -> String {
+    "+".to_string()
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct SmtpConfig {
     pub username: String,
+    /// IMAP认证方式: password / xoauth2
+    #[serde(default = "default_imap_auth_type")]
+    pub auth_type: String,
+    /// 使用 xoauth2 认证时可以省略
+    #[serde(default)]
     pub password: String,
     pub imap_server: String,
     pub imap_port: u16,
+    /// IMAP连接方式: tls（端口993）/ starttls（端口143）/ none（仅用于本地测试服务器）
+    #[serde(default = "default_imap_security")]
+    pub security: String,
+    /// 额外信任的CA证书（PEM文件路径）
+    pub ca_cert: Option<String>,
+    /// 接受无效或自签名的证书（不安全，仅用于本地测试服务器）
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
     #[allow(dead_code)]
     pub smtp_server: String,
     #[allow(dead_code)]
     pub smtp_port: u16,
+    /// 监听验证码的文件夹
+    #[serde(default = "default_folders")]
+    pub folders: Vec<String>,
+    /// auth_type = "xoauth2" 时使用的OAuth2配置
+    pub oauth2: Option<OAuth2Config>,
+}
+
+fn default_imap_security() -> String {
+    "tls".to_string()
+}
+
+fn default_imap_auth_type() -> String {
+    "password".to_string()
+}
+
+fn default_folders() -> Vec<String> {
+    vec!["INBOX".to_string()]
+}
+
+/// IMAP OAuth2 (XOAUTH2) 配置
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct OAuth2Config {
+    /// 预设的服务商: google / microsoft，未设置时需要手动填写各端点
+    pub provider: Option<String>,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    /// 授权页面地址（浏览器授权流程）
+    pub auth_url: Option<String>,
+    /// 令牌地址
+    pub token_url: Option<String>,
+    /// 设备授权地址（设备码流程）
+    pub device_auth_url: Option<String>,
+    /// 申请的权限范围，多个权限以空格分隔
+    pub scope: Option<String>,
+}
+
+/// 验证码提取配置
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ExtractConfig {
+    /// 自定义提取规则，优先于内置规则
+    #[serde(default)]
+    pub rules: Vec<ExtractRule>,
+}
+
+/// 自定义验证码提取规则
+#[derive(Debug, Deserialize, Clone)]
+pub struct ExtractRule {
+    /// 规则名称，仅用于显示
+    pub name: Option<String>,
+    /// 发件人正则，只对匹配的发件人生效；为空时对所有邮件生效
+    pub sender: Option<String>,
+    /// 提取验证码的正则表达式
+    pub pattern: String,
+    /// 验证码所在的捕获组
+    #[serde(default = "default_rule_group")]
+    pub group: usize,
+    /// 优先级，数值越大越先尝试（内置规则为 0-60）
+    #[serde(default = "default_rule_priority")]
+    pub priority: i32,
+}
+
+fn default_rule_group() -> usize {
+    1
+}
+
+fn default_rule_priority() -> i32 {
+    100
 }
 
 impl Config {
@@ -63,12 +213,40 @@ impl Config {
         file.read_to_string(&mut contents)
             .with_context(|| "无法读取配置文件内容")?;
 
-        toml::from_str(&contents).with_context(|| "解析配置文件失败")
+        let config: Self = toml::from_str(&contents).with_context(|| "解析配置文件失败")?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 读取配置文件，文件不存在时返回 None，供不需要账户信息的命令使用
+    pub fn load_optional() -> Result<Option<Self>> {
+        let home = dirs::home_dir().context("无法获取用户主目录")?;
+        if !home.join(".config/cfmail/config.toml").exists() {
+            return Ok(None);
+        }
+        Self::load().map(Some)
+    }
+
+    /// 检查无法由类型表达的取值范围
+    fn validate(&self) -> Result<()> {
+        let max = MAX_HMAC_TOKEN_LENGTH;
+        if !(1..=max).contains(&self.alias.deterministic_length) {
+            return Err(anyhow!(
+                "配置错误: deterministic_length 必须在 1 到 {} 之间",
+                max
+            ));
+        }
+        if self.alias.site_suffix == "hmac" && self.alias.site_suffix_length > max {
+            return Err(anyhow!(
+                "配置错误: site_suffix = \"hmac\" 时 site_suffix_length 不能超过 {}",
+                max
+            ));
+        }
+        Ok(())
     }
 
     pub fn init() -> Result<std::path::PathBuf> {
-        let mut config_dir = dirs::home_dir()
-            .context("无法获取用户主目录")?;
+        let mut config_dir = dirs::home_dir().context("无法获取用户主目录")?;
         config_dir.push(".config");
         config_dir.push("cfmail");
 
@@ -89,6 +267,7 @@ impl Config {
                 OpenOptions::new()
                     .write(true)
                     .create(true)
+                    .truncate(true)
                     .mode(0o600)
                     .open(&config_path)
             }
@@ -97,6 +276,7 @@ impl Config {
                 OpenOptions::new()
                     .write(true)
                     .create(true)
+                    .truncate(true)
                     .open(&config_path)
             }
         }
@@ -114,13 +294,36 @@ email = "您的Cloudflare账户邮箱"
 [email]
 domain = "您的自定义域名"
 target_email = "您的目标邮箱"
+# 子域名别名（需在Cloudflare中为子域名启用邮件路由，可运行 cfmail setup-domain）
+# subdomain_template = "{label}"
+# subdomains = ["team"]
 
 # 别名生成配置
 [alias]
 prefix_mode = "random"
 custom_prefixes = ["support", "contact", "info"]
 random_length = 8
+# 可选: alphabetic / alphanumeric / numeric / words / pronounceable（均为小写）
 random_charset = "alphabetic"
+# 排除容易混淆的字符 0/o、1/l
+exclude_ambiguous = false
+# words 字符集: 单词数量、分隔符和末尾数字位数，例如 quiet-otter-42
+word_count = 2
+word_separator = "-"
+word_digits = 2
+# generate --for 派生别名的后缀: none / random / hmac（hmac 需要设置 secret）
+site_suffix = "none"
+site_suffix_length = 4
+# secret = "团队共享的密钥"
+# 确定性模式 (prefix_mode = "deterministic") 的前缀长度，由 secret 和 --label 计算
+deterministic_length = 10
+# generate --subaddress 使用的基础别名（需已存在路由规则）和分隔符
+# subaddress_base = "me@example.com"
+subaddress_separator = "+"
+# 模板模式 (prefix_mode = "template") 使用的模板
+# 可用占位符: {label} {rand} {rand:N} {date} {date:%y%m} {user} {word}
+# random_charset = "words" 时 {rand} 按 word_count 生成单词，不能使用 {rand:N}
+# template = "{label}.{date:%y%m}.{rand:4}"
 
 # SMTP和IMAP配置
 [smtp]
@@ -128,8 +331,34 @@ username = "您的邮箱地址"
 password = "您的邮箱密码"
 imap_server = "imap.example.com"
 imap_port = 993
+# IMAP连接方式: tls（默认，端口993）/ starttls（端口143）/ none（明文，仅用于本地测试服务器）
+security = "tls"
+# ca_cert = "/path/to/ca.pem"       # 额外信任的CA证书
+# accept_invalid_certs = false      # 接受自签名证书（不安全）
 smtp_server = "smtp.example.com"
 smtp_port = 587
+# 监听的文件夹（可运行 cfmail folders 查看服务器上的文件夹名称）
+# folders = ["INBOX", "[Gmail]/Spam"]
+# IMAP认证方式: password / xoauth2（Gmail、Outlook 推荐使用 xoauth2，配置后运行 cfmail auth login）
+auth_type = "password"
+
+# OAuth2 配置（auth_type = "xoauth2" 时使用）
+# [smtp.oauth2]
+# provider = "google"               # google / microsoft，其他服务商请填写下面的端点
+# client_id = "您的OAuth客户端ID"
+# client_secret = "您的OAuth客户端密钥"
+# auth_url = "https://example.com/oauth2/authorize"
+# token_url = "https://example.com/oauth2/token"
+# device_auth_url = "https://example.com/oauth2/device"
+# scope = "https://mail.example.com/"
+
+# 自定义验证码提取规则（可选，可以有多条）
+# [[extract.rules]]
+# name = "vendor-pin"
+# sender = '@vendor\.com$'          # 发件人正则，省略时对所有邮件生效
+# pattern = 'PIN:\s*([A-Z0-9]{2}-[A-Z0-9]{2}-[A-Z0-9]{2})'
+# group = 1                         # 验证码所在的捕获组
+# priority = 100                    # 数值越大越先尝试
 "#;
 
         file.write_all(template.as_bytes())
@@ -139,8 +368,7 @@ smtp_port = 587
     }
 
     fn find_config_file() -> Result<std::path::PathBuf> {
-        let mut config_dir = dirs::home_dir()
-            .context("无法获取用户主目录")?;
+        let mut config_dir = dirs::home_dir().context("无法获取用户主目录")?;
         config_dir.push(".config");
         config_dir.push("cfmail");
 
@@ -161,3 +389,52 @@ smtp_port = 587
         ))
     }
 }
+
+/// 获取配置目录（~/.config/cfmail），不存在时创建
+pub fn config_dir() -> Result<std::path::PathBuf> {
+    let mut config_dir = dirs::home_dir().context("无法获取用户主目录")?;
+    config_dir.push(".config");
+    config_dir.push("cfmail");
+
+    if !config_dir.exists() {
+        fs::create_dir_all(&config_dir)
+            .with_context(|| format!("无法创建配置目录: {}", config_dir.display()))?;
+    }
+
+    Ok(config_dir)
+}
+
+/// 写入包含敏感信息的文件，在Unix系统上权限为0600（仅所有者可读写）
+pub fn write_private_file(path: &std::path::Path, contents: &str) -> Result<()> {
+    let mut file = {
+        #[cfg(unix)]
+        {
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(path)
+        }
+        #[cfg(not(unix))]
+        {
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)
+        }
+    }
+    .with_context(|| format!("无法写入文件: {}", path.display()))?;
+
+    // mode 只在创建文件时生效，已存在的文件需要单独收紧权限
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("无法设置文件权限: {}", path.display()))?;
+    }
+
+    file.write_all(contents.as_bytes())
+        .with_context(|| format!("无法写入文件: {}", path.display()))
+}
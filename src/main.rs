@@ -6,16 +6,17 @@ mod util;
 
 use crate::util::i18n;
 use anyhow::Result;
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use colored::*;
-use prettytable;
-use service::mail_monitor::{CodeType, MailMonitor, MonitorOptions};
+use service::mail_monitor::{
+    CodeResult, CodeScanner, CodeType, DEFAULT_POLL_INTERVAL, DEFAULT_TIMEOUT, MailMonitor,
+    MessageAction, MonitorOptions, get_message_subject,
+};
+use service::oauth::{self, AuthFlow};
+use service::totp::{TotpEntry, TotpStore};
 use std::io::{self, BufRead, Write};
-
-/// 获取根据当前语言设置的命令描述
-fn t(key: &str) -> String {
-    i18n::translate(key)
-}
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Clone, ValueEnum, Debug)]
 enum CodeTypeArg {
@@ -37,6 +38,121 @@ impl From<CodeTypeArg> for CodeType {
     }
 }
 
+/// 解析时长参数，支持 s/m/h 单位，未指定单位时按秒计算
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "s"),
+    };
+
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration: {}", value))?;
+    let seconds = match unit {
+        "s" => number,
+        "m" => number * 60,
+        "h" => number * 60 * 60,
+        _ => return Err(format!("invalid duration unit: {}", unit)),
+    };
+
+    Ok(Duration::from_secs(seconds))
+}
+
+/// 找到结果后如何处理来源邮件
+#[derive(Args, Debug)]
+struct MessageActionArgs {
+    /// Mark the matched message as seen (default)
+    #[arg(long, conflicts_with = "keep_unseen")]
+    mark_seen: bool,
+
+    /// Leave the matched message unseen
+    #[arg(long)]
+    keep_unseen: bool,
+
+    /// Move the matched message to this folder
+    #[arg(long, value_name = "FOLDER", conflicts_with = "delete")]
+    move_to: Option<String>,
+
+    /// Delete the matched message
+    #[arg(long)]
+    delete: bool,
+}
+
+impl MessageActionArgs {
+    fn mark_seen(&self) -> bool {
+        !self.keep_unseen
+    }
+
+    fn action(&self) -> MessageAction {
+        match (&self.move_to, self.delete) {
+            (Some(folder), _) => MessageAction::MoveTo(folder.clone()),
+            (None, true) => MessageAction::Delete,
+            (None, false) => MessageAction::Keep,
+        }
+    }
+}
+
+/// 注册流程结束后如何处理别名
+#[derive(Clone, ValueEnum, Debug, PartialEq, Eq)]
+enum AfterSignupArg {
+    /// 保留别名
+    Keep,
+    /// 删除别名的路由规则
+    Delete,
+    /// 禁用别名的路由规则
+    Disable,
+}
+
+/// TOTP 子命令
+#[derive(Subcommand)]
+enum TotpAction {
+    /// Store a TOTP secret under a label
+    ///
+    /// The secret is written to totp.toml in plain text (file mode 0600).
+    Add {
+        /// Label, usually the alias address or service name
+        label: String,
+
+        /// Base32 secret or otpauth:// URI (prompted for when omitted)
+        secret: Option<String>,
+
+        /// Number of digits (overrides the URI)
+        #[arg(long)]
+        digits: Option<u32>,
+
+        /// Time step in seconds (overrides the URI)
+        #[arg(long)]
+        period: Option<u64>,
+    },
+    /// List stored TOTP labels
+    List,
+    /// Remove a stored TOTP secret
+    Remove {
+        /// Label of the secret to remove
+        label: String,
+    },
+}
+
+/// OAuth2 子命令
+#[derive(Subcommand)]
+enum AuthAction {
+    /// Authorize cfmail to read the mailbox and store the refresh token
+    Login {
+        /// Use the device code flow (enter a code on any device)
+        #[arg(long, conflicts_with = "browser")]
+        device: bool,
+
+        /// Use the browser flow with a local redirect
+        #[arg(long)]
+        browser: bool,
+    },
+    /// Show whether a token is stored for the mailbox
+    Status,
+    /// Remove the stored token for the mailbox
+    Logout,
+}
+
 #[derive(Parser)]
 #[command(author, version, about = "Cloudflare Email Alias Generator", long_about = None)]
 struct Cli {
@@ -55,6 +171,38 @@ enum Commands {
         /// Custom prefix (optional)
         #[arg(short, long)]
         prefix: Option<String>,
+
+        /// Service label used by alias templates
+        #[arg(short, long)]
+        label: Option<String>,
+
+        /// Derive the prefix from a site URL or service name
+        #[arg(long = "for", value_name = "URL", conflicts_with = "prefix")]
+        site: Option<String>,
+
+        /// Generate a base+tag subaddress of an existing alias
+        #[arg(long, conflicts_with = "prefix")]
+        subaddress: bool,
+
+        /// Base alias for --subaddress
+        #[arg(long, requires = "subaddress")]
+        base: Option<String>,
+
+        /// Create the alias under this subdomain
+        #[arg(long, conflicts_with = "subaddress")]
+        subdomain: Option<String>,
+
+        /// Wait for a verification code sent to the new alias
+        #[arg(long)]
+        watch: bool,
+
+        /// Timeout in seconds for --watch
+        #[arg(short, long, default_value_t = DEFAULT_TIMEOUT, requires = "watch")]
+        timeout: u64,
+
+        /// Polling interval in seconds for --watch (used when the server does not support IDLE)
+        #[arg(long, default_value_t = DEFAULT_POLL_INTERVAL, requires = "watch")]
+        poll_interval: u64,
     },
     /// List all configured email aliases
     List,
@@ -66,24 +214,171 @@ enum Commands {
         #[arg(short, long)]
         length: Option<usize>,
 
-        /// Verification code type
-        #[arg(short, long, value_enum, default_value_t = CodeTypeArg::Numeric)]
-        code_type: CodeTypeArg,
+        /// Verification code type (built-in rules default to numeric)
+        #[arg(short, long, value_enum)]
+        code_type: Option<CodeTypeArg>,
+
+        /// Sender filter
+        #[arg(short, long)]
+        from: Option<String>,
+
+        /// Recipient filter matched against To, Cc, Delivered-To and X-Forwarded-To (a base alias also matches its subaddresses)
+        #[arg(long)]
+        to: Option<String>,
+
+        /// Also accept mail sent this long before the watch started (e.g. 90s, 2m, 1h)
+        #[arg(long, value_parser = parse_duration)]
+        since: Option<Duration>,
+
+        /// Folder to watch, repeatable (defaults to smtp.folders in the config)
+        #[arg(long = "folder", value_name = "FOLDER")]
+        folders: Vec<String>,
+
+        /// Timeout in seconds
+        #[arg(short, long, default_value_t = DEFAULT_TIMEOUT)]
+        timeout: u64,
+
+        /// Polling interval in seconds (used when the server does not support IDLE)
+        #[arg(long, default_value_t = DEFAULT_POLL_INTERVAL)]
+        poll_interval: u64,
 
+        #[command(flatten)]
+        post: MessageActionArgs,
+    },
+    /// Monitor for verification link emails
+    WatchLink {
         /// Sender filter
         #[arg(short, long)]
         from: Option<String>,
 
+        /// Recipient filter matched against To, Cc, Delivered-To and X-Forwarded-To
+        #[arg(long)]
+        to: Option<String>,
+
+        /// Also accept mail sent this long before the watch started (e.g. 90s, 2m, 1h)
+        #[arg(long, value_parser = parse_duration)]
+        since: Option<Duration>,
+
+        /// Folder to watch, repeatable (defaults to smtp.folders in the config)
+        #[arg(long = "folder", value_name = "FOLDER")]
+        folders: Vec<String>,
+
         /// Timeout in seconds
-        #[arg(short, long, default_value_t = 300)]
+        #[arg(short, long, default_value_t = DEFAULT_TIMEOUT)]
         timeout: u64,
 
-        /// Polling interval in seconds
-        #[arg(long, default_value_t = 3)]
+        /// Polling interval in seconds (used when the server does not support IDLE)
+        #[arg(long, default_value_t = DEFAULT_POLL_INTERVAL)]
         poll_interval: u64,
+
+        /// Open the link in the default browser
+        #[arg(long)]
+        open: bool,
+
+        #[command(flatten)]
+        post: MessageActionArgs,
+    },
+    /// Run code extraction on saved .eml files
+    ///
+    /// Custom rules from config.toml are used when it exists; otherwise only
+    /// the built-in rules apply.
+    Extract {
+        /// An .eml file or a directory of .eml files
+        path: PathBuf,
+
+        /// Verification code length
+        #[arg(short, long)]
+        length: Option<usize>,
+
+        /// Verification code type (built-in rules default to numeric)
+        #[arg(short, long, value_enum)]
+        code_type: Option<CodeTypeArg>,
+    },
+    /// Generate TOTP codes for stored two-factor secrets
+    ///
+    /// Secrets are stored unencrypted in totp.toml in the config directory,
+    /// protected only by its file permissions (0600).
+    #[command(args_conflicts_with_subcommands = true)]
+    Totp {
+        /// Label of the secret to generate a code for
+        label: Option<String>,
+
+        #[command(subcommand)]
+        action: Option<TotpAction>,
+    },
+    /// List the folders on the IMAP server
+    Folders,
+    /// Manage OAuth2 (XOAUTH2) sign-in for the IMAP mailbox
+    Auth {
+        #[command(subcommand)]
+        action: AuthAction,
     },
     /// Initialize configuration file
     Init,
+    /// Check whether an alias was derived from a label
+    VerifyAlias {
+        /// Email alias to verify
+        address: String,
+
+        /// Service label the alias should belong to
+        label: String,
+    },
+    /// Enable email routing for the domain and its subdomains
+    SetupDomain {
+        /// Additional subdomain to enable (repeatable)
+        #[arg(long)]
+        subdomain: Vec<String>,
+    },
+    /// Generate an alias, then wait for the signup verification code sent to it
+    Signup {
+        /// Service label used by alias templates
+        #[arg(short, long)]
+        label: Option<String>,
+
+        /// Derive the prefix from a site URL or service name
+        #[arg(long = "for", value_name = "URL")]
+        site: Option<String>,
+
+        /// Create the alias under this subdomain
+        #[arg(long)]
+        subdomain: Option<String>,
+
+        /// Sender filter
+        #[arg(short, long)]
+        from: Option<String>,
+
+        /// Folder to watch, repeatable (defaults to smtp.folders in the config)
+        #[arg(long = "folder", value_name = "FOLDER")]
+        folders: Vec<String>,
+
+        /// Timeout in seconds
+        #[arg(short, long, default_value_t = DEFAULT_TIMEOUT)]
+        timeout: u64,
+
+        /// Polling interval in seconds (used when the server does not support IDLE)
+        #[arg(long, default_value_t = DEFAULT_POLL_INTERVAL)]
+        poll_interval: u64,
+
+        /// What to do with the alias once the watch ends
+        #[arg(long, value_enum, default_value_t = AfterSignupArg::Keep)]
+        after: AfterSignupArg,
+
+        #[command(flatten)]
+        post: MessageActionArgs,
+    },
+    /// Sync aliases from a manifest file
+    ///
+    /// The manifest is the complete list of cfmail-managed aliases: rules
+    /// created by cfmail (including `cfmail create`) that are missing from it
+    /// are DELETED. Rules created outside cfmail are never changed.
+    Apply {
+        /// Path to the alias manifest (TOML)
+        file: PathBuf,
+
+        /// Apply without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
 }
 
 #[tokio::main]
@@ -112,7 +407,17 @@ async fn main() -> Result<()> {
     }
 
     match &cli.command {
-        Commands::Generate { prefix } => {
+        Commands::Generate {
+            prefix,
+            label,
+            site,
+            subaddress,
+            base,
+            subdomain,
+            watch,
+            timeout,
+            poll_interval,
+        } => {
             ui::print_module_header(&i18n::translate("modules.generate"));
 
             // 加载配置
@@ -132,54 +437,42 @@ async fn main() -> Result<()> {
                 }
             };
 
-            // 生成邮箱别名
-            let spinner = ui::create_spinner(&i18n::translate("ui.generating_alias"));
-            let email_alias = match service::email::generate_alias(&cfg, prefix.clone()) {
-                Ok(alias) => {
-                    ui::spinner_success(
-                        &spinner,
-                        &i18n::translate_args("ui.alias_generated", &[("alias", &alias)]),
-                    );
-                    alias
-                }
-                Err(e) => {
-                    ui::spinner_error(
-                        &spinner,
-                        &i18n::translate_args("ui.alias_failed", &[("error", &e.to_string())]),
-                    );
-                    return Err(e);
-                }
-            };
-
-            // 创建Cloudflare邮件路由
-            let spinner = ui::create_spinner(&i18n::translate("ui.configuring_route"));
-            let cf_client = api::cloudflare::CloudflareClient::new(&cfg);
-            match cf_client.create_email_route(&email_alias).await {
-                Ok(_) => {
-                    ui::spinner_success(&spinner, &i18n::translate("ui.route_configured"));
-                }
-                Err(e) => {
-                    ui::spinner_error(
-                        &spinner,
-                        &i18n::translate_args("ui.route_failed", &[("error", &e.to_string())]),
-                    );
-                    return Err(e);
-                }
-            }
-
-            // 复制到剪贴板
-            if let Err(e) = util::clipboard::copy_to_clipboard(&email_alias) {
+            // 在调用API之前校验自定义前缀
+            if let Some(prefix) = prefix
+                && let Err(e) = service::email::format_alias(&cfg, prefix)
+            {
                 ui::print_error(&i18n::translate_args(
-                    "ui.clipboard_failed",
+                    "ui.alias_failed",
                     &[("error", &e.to_string())],
                 ));
-            } else {
-                ui::print_success(&i18n::translate("ui.clipboard_success"));
+                return Err(e);
             }
 
-            // 显示最终结果
-            ui::print_result_box(&i18n::translate("ui.result_title"), &email_alias);
-            ui::print_info(&i18n::translate("ui.alias_ready"));
+            // 生成别名并创建路由
+            let alias_options = service::email::AliasOptions {
+                prefix: prefix.clone(),
+                label: label.clone(),
+                site: site.clone(),
+                subaddress: *subaddress,
+                base: base.clone(),
+                subdomain: subdomain.clone(),
+            };
+            let email_alias = create_alias(&cfg, &alias_options).await?;
+
+            // 监听发往新别名的验证码
+            if *watch {
+                ui::print_info(&i18n::translate_args(
+                    "ui.watching_alias",
+                    &[("alias", &email_alias)],
+                ));
+                let options = MonitorOptions {
+                    to_filter: Some(email_alias.clone()),
+                    timeout: *timeout,
+                    poll_interval: *poll_interval,
+                    ..MonitorOptions::default()
+                };
+                watch_code(&cfg, options).await?;
+            }
         }
         Commands::List => {
             ui::print_module_header(&i18n::translate("modules.list"));
@@ -451,8 +744,12 @@ async fn main() -> Result<()> {
             length,
             code_type,
             from,
+            to,
+            since,
+            folders,
             timeout,
             poll_interval,
+            post,
         } => {
             ui::print_module_header(&i18n::translate("modules.watch"));
 
@@ -476,71 +773,1111 @@ async fn main() -> Result<()> {
             // 创建邮件监听器选项
             let options = MonitorOptions {
                 code_length: *length,
-                code_type: code_type.clone().into(),
+                code_type: code_type.clone().map(Into::into),
                 from_filter: from.clone(),
+                to_filter: to.clone(),
+                since: *since,
                 timeout: *timeout,
                 poll_interval: *poll_interval,
+                mark_seen: post.mark_seen(),
+                action: post.action(),
+                folders: folders.clone(),
             };
 
-            // 创建邮件监听器
-            let monitor = MailMonitor::new(&cfg, options);
+            watch_code(&cfg, options).await?;
+        }
+        Commands::WatchLink {
+            from,
+            to,
+            since,
+            folders,
+            timeout,
+            poll_interval,
+            open,
+            post,
+        } => {
+            ui::print_module_header(&i18n::translate("modules.watch_link"));
 
-            // 等待验证码
-            let spinner = ui::create_spinner(&i18n::translate("ui.waiting_code"));
-            match monitor.wait_for_code() {
-                Ok(result) => {
-                    ui::spinner_success(&spinner, &i18n::translate("ui.code_found"));
+            // 加载配置
+            let spinner = ui::create_spinner(&i18n::translate("ui.loading_config"));
+            let cfg = match config::Config::load() {
+                Ok(cfg) => {
+                    ui::spinner_success(&spinner, &i18n::translate("ui.config_loaded"));
+                    cfg
+                }
+                Err(e) => {
+                    ui::spinner_error(
+                        &spinner,
+                        &i18n::translate_args("ui.config_failed", &[("error", &e.to_string())]),
+                    );
+                    ui::print_info(&i18n::translate("ui.config_hint"));
+                    return Err(e);
+                }
+            };
 
-                    // 复制到剪贴板
-                    if let Err(e) = util::clipboard::copy_to_clipboard(&result.code) {
-                        ui::print_error(&i18n::translate_args(
-                            "ui.clipboard_failed",
-                            &[("error", &e.to_string())],
-                        ));
-                    } else {
-                        ui::print_success(&i18n::translate("ui.code_copied"));
-                    }
+            let options = MonitorOptions {
+                from_filter: from.clone(),
+                to_filter: to.clone(),
+                since: *since,
+                timeout: *timeout,
+                poll_interval: *poll_interval,
+                mark_seen: post.mark_seen(),
+                action: post.action(),
+                folders: folders.clone(),
+                ..MonitorOptions::default()
+            };
+            let monitor = MailMonitor::new(&cfg, options);
 
-                    // 显示最终结果
-                    ui::print_result_box(&i18n::translate("ui.code_result"), &result.code);
+            // 等待验证链接
+            let spinner = ui::create_spinner(&i18n::translate("ui.waiting_link"));
+            let result = match monitor.wait_for_link().await {
+                Ok(result) => {
+                    ui::spinner_success(&spinner, &i18n::translate("ui.link_found"));
+                    result
                 }
                 Err(e) => {
                     ui::spinner_error(
                         &spinner,
-                        &i18n::translate_args("ui.code_failed", &[("error", &e.to_string())]),
+                        &i18n::translate_args("ui.link_failed", &[("error", &e.to_string())]),
                     );
                     return Err(e);
                 }
+            };
+
+            // 复制到剪贴板
+            if let Err(e) = util::clipboard::copy_to_clipboard(&result.url) {
+                ui::print_error(&i18n::translate_args(
+                    "ui.clipboard_failed",
+                    &[("error", &e.to_string())],
+                ));
+            } else {
+                ui::print_success(&i18n::translate("ui.link_copied"));
+            }
+
+            // 在浏览器中打开
+            if *open {
+                match util::browser::open_url(&result.url) {
+                    Ok(_) => ui::print_success(&i18n::translate("ui.link_opened")),
+                    Err(e) => ui::print_error(&i18n::translate_args(
+                        "ui.link_open_failed",
+                        &[("error", &e.to_string())],
+                    )),
+                }
             }
+
+            // 显示最终结果
+            ui::print_result_box(&i18n::translate("ui.link_result"), &result.url);
         }
-        Commands::Init => {
-            ui::print_module_header(&i18n::translate("modules.init"));
+        Commands::Extract {
+            path,
+            length,
+            code_type,
+        } => {
+            ui::print_module_header(&i18n::translate("modules.extract"));
 
-            // 初始化配置文件
-            let spinner = ui::create_spinner(&i18n::translate("ui.initializing_config"));
-            match config::Config::init() {
-                Ok(path) => {
-                    ui::spinner_success(&spinner, &i18n::translate("ui.config_initialized"));
-                    // 显示结果
-                    ui::print_result_box(
-                        &i18n::translate("ui.config_location"),
-                        &path.to_string_lossy(),
+            // 加载配置（自定义提取规则），没有配置文件时只使用内置规则
+            let spinner = ui::create_spinner(&i18n::translate("ui.loading_config"));
+            let cfg = match config::Config::load_optional() {
+                Ok(Some(cfg)) => {
+                    ui::spinner_success(&spinner, &i18n::translate("ui.config_loaded"));
+                    Some(cfg)
+                }
+                Ok(None) => {
+                    ui::spinner_success(
+                        &spinner,
+                        &i18n::translate("commands.extract.builtin_rules"),
                     );
-                    ui::print_info(&i18n::translate("ui.config_edit_hint"));
+                    None
                 }
                 Err(e) => {
                     ui::spinner_error(
                         &spinner,
-                        &i18n::translate_args(
-                            "ui.config_init_failed",
-                            &[("error", &e.to_string())],
-                        ),
+                        &i18n::translate_args("ui.config_failed", &[("error", &e.to_string())]),
                     );
+                    ui::print_info(&i18n::translate("ui.config_hint"));
                     return Err(e);
                 }
             };
+            let extract_config = cfg.map(|cfg| cfg.extract).unwrap_or_default();
+
+            // 收集邮件文件
+            let files = if path.is_dir() {
+                let mut files: Vec<PathBuf> = std::fs::read_dir(path)?
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|file| {
+                        file.extension()
+                            .is_some_and(|ext| ext.eq_ignore_ascii_case("eml"))
+                    })
+                    .collect();
+                files.sort();
+                files
+            } else {
+                vec![path.clone()]
+            };
+
+            let options = MonitorOptions {
+                code_length: *length,
+                code_type: code_type.clone().map(Into::into),
+                ..MonitorOptions::default()
+            };
+            let scanner = CodeScanner::new(&extract_config, &options)?;
+
+            let mut found = 0;
+            for file in &files {
+                ui::print_info(&file.display().to_string());
+
+                let raw = match std::fs::read(file) {
+                    Ok(raw) => raw,
+                    Err(e) => {
+                        ui::print_error(&i18n::translate_args(
+                            "commands.extract.read_failed",
+                            &[("error", &e.to_string())],
+                        ));
+                        continue;
+                    }
+                };
+                let Some(mail) = mail_parser::Message::parse(&raw) else {
+                    ui::print_error(&i18n::translate("commands.extract.parse_failed"));
+                    continue;
+                };
+
+                println!(
+                    "  {}",
+                    i18n::translate_args(
+                        "commands.extract.subject",
+                        &[("subject", &get_message_subject(&mail))]
+                    )
+                );
+
+                let codes = scanner.scan(&mail);
+                if codes.is_empty() {
+                    ui::print_warning(&i18n::translate("commands.extract.no_code"));
+                    continue;
+                }
+
+                // 候选验证码及评分依据
+                let rows: Vec<Vec<String>> = codes
+                    .iter()
+                    .map(|code| {
+                        vec![
+                            code.code.clone(),
+                            code.score.to_string(),
+                            format!("{:.0}%", code.confidence * 100.0),
+                            code.rules.join(", "),
+                            code.reasons.join(", "),
+                        ]
+                    })
+                    .collect();
+                ui::print_table(
+                    &[
+                        &i18n::translate("commands.extract.code"),
+                        &i18n::translate("commands.extract.score"),
+                        &i18n::translate("commands.extract.confidence"),
+                        &i18n::translate("commands.extract.rules"),
+                        &i18n::translate("commands.extract.reasons"),
+                    ],
+                    &rows,
+                );
+
+                let best = &codes[0];
+                let message = i18n::translate_args(
+                    "commands.extract.chosen",
+                    &[
+                        ("code", &best.code),
+                        ("confidence", &format!("{:.0}", best.confidence * 100.0)),
+                    ],
+                );
+                if best.confidence < service::extractor::LOW_CONFIDENCE {
+                    ui::print_warning(&message);
+                } else {
+                    ui::print_success(&message);
+                }
+                found += 1;
+            }
+
+            println!();
+            ui::print_info(&i18n::translate_args(
+                "commands.extract.summary",
+                &[
+                    ("found", &found.to_string()),
+                    ("total", &files.len().to_string()),
+                ],
+            ));
         }
-    }
+        Commands::Totp { label, action } => {
+            ui::print_module_header(&i18n::translate("modules.totp"));
 
-    Ok(())
+            let mut store = TotpStore::load()?;
+            match (action, label) {
+                (
+                    Some(TotpAction::Add {
+                        label,
+                        secret,
+                        digits,
+                        period,
+                    }),
+                    _,
+                ) => {
+                    // 未在命令行提供密钥时隐藏输入，避免留在 shell 历史中
+                    let secret = match secret {
+                        Some(secret) => secret.clone(),
+                        None => dialoguer::Password::new()
+                            .with_prompt(i18n::translate("commands.totp.secret_prompt"))
+                            .interact()?,
+                    };
+
+                    let mut entry = TotpEntry::parse(label, &secret)?;
+                    if let Some(digits) = digits {
+                        entry.digits = *digits;
+                    }
+                    if let Some(period) = period {
+                        entry.period = *period;
+                    }
+                    entry.validate()?;
+                    let (code, _) = entry.current_code()?;
+
+                    store.add(entry)?;
+                    store.save()?;
+                    ui::print_success(&i18n::translate_args(
+                        "commands.totp.added",
+                        &[("label", label)],
+                    ));
+                    ui::print_info(&i18n::translate_args(
+                        "commands.totp.check_code",
+                        &[("code", &code)],
+                    ));
+                }
+                (Some(TotpAction::List), _) => {
+                    if store.entries.is_empty() {
+                        ui::print_warning(&i18n::translate("commands.totp.empty"));
+                        return Ok(());
+                    }
+
+                    // 不显示密钥
+                    let rows: Vec<Vec<String>> = store
+                        .entries
+                        .iter()
+                        .map(|entry| {
+                            vec![
+                                entry.label.clone(),
+                                entry.issuer.clone().unwrap_or_default(),
+                                entry.digits.to_string(),
+                                format!("{}s", entry.period),
+                                entry.algorithm.clone(),
+                            ]
+                        })
+                        .collect();
+                    ui::print_table(
+                        &[
+                            &i18n::translate("commands.totp.label"),
+                            &i18n::translate("commands.totp.issuer"),
+                            &i18n::translate("commands.totp.digits"),
+                            &i18n::translate("commands.totp.period"),
+                            &i18n::translate("commands.totp.algorithm"),
+                        ],
+                        &rows,
+                    );
+                }
+                (Some(TotpAction::Remove { label }), _) => {
+                    if !store.remove(label) {
+                        return Err(anyhow::anyhow!(i18n::translate_args(
+                            "commands.totp.not_found",
+                            &[("label", label)],
+                        )));
+                    }
+                    store.save()?;
+                    ui::print_success(&i18n::translate_args(
+                        "commands.totp.removed",
+                        &[("label", label)],
+                    ));
+                }
+                (None, Some(label)) => {
+                    let Some(entry) = store.get(label) else {
+                        return Err(anyhow::anyhow!(i18n::translate_args(
+                            "commands.totp.not_found",
+                            &[("label", label)],
+                        )));
+                    };
+                    let (code, remaining) = entry.current_code()?;
+
+                    if let Err(e) = util::clipboard::copy_to_clipboard(&code) {
+                        ui::print_error(&i18n::translate_args(
+                            "ui.clipboard_failed",
+                            &[("error", &e.to_string())],
+                        ));
+                    } else {
+                        ui::print_success(&i18n::translate("ui.code_copied"));
+                    }
+
+                    ui::print_info(&i18n::translate_args(
+                        "commands.totp.remaining",
+                        &[("seconds", &remaining.to_string())],
+                    ));
+                    ui::print_result_box(&i18n::translate("commands.totp.result"), &code);
+                }
+                (None, None) => {
+                    return Err(anyhow::anyhow!(i18n::translate(
+                        "commands.totp.missing_label"
+                    )));
+                }
+            }
+        }
+        Commands::Folders => {
+            ui::print_module_header(&i18n::translate("modules.folders"));
+
+            // 加载配置
+            let spinner = ui::create_spinner(&i18n::translate("ui.loading_config"));
+            let cfg = match config::Config::load() {
+                Ok(cfg) => {
+                    ui::spinner_success(&spinner, &i18n::translate("ui.config_loaded"));
+                    cfg
+                }
+                Err(e) => {
+                    ui::spinner_error(
+                        &spinner,
+                        &i18n::translate_args("ui.config_failed", &[("error", &e.to_string())]),
+                    );
+                    return Err(e);
+                }
+            };
+
+            // 获取文件夹列表
+            let spinner = ui::create_spinner(&i18n::translate("commands.folders.fetching"));
+            let monitor = MailMonitor::new(&cfg, MonitorOptions::default());
+            let folders = match monitor.list_folders().await {
+                Ok(folders) => {
+                    ui::spinner_success(
+                        &spinner,
+                        &i18n::translate_args(
+                            "commands.folders.fetched",
+                            &[("count", &folders.len().to_string())],
+                        ),
+                    );
+                    folders
+                }
+                Err(e) => {
+                    ui::spinner_error(
+                        &spinner,
+                        &i18n::translate_args(
+                            "commands.folders.failed",
+                            &[("error", &format!("{:#}", e))],
+                        ),
+                    );
+                    return Err(e);
+                }
+            };
+
+            let rows: Vec<Vec<String>> = folders
+                .iter()
+                .map(|folder| {
+                    let watched = cfg.smtp.folders.iter().any(|name| folder.matches(name));
+                    vec![
+                        folder.name.clone(),
+                        folder.attributes.join(" "),
+                        if watched { "✓" } else { "" }.to_string(),
+                    ]
+                })
+                .collect();
+            ui::print_table(
+                &[
+                    &i18n::translate("commands.folders.name"),
+                    &i18n::translate("commands.folders.attributes"),
+                    &i18n::translate("commands.folders.watched"),
+                ],
+                &rows,
+            );
+            ui::print_info(&i18n::translate("commands.folders.hint"));
+        }
+        Commands::Auth { action } => {
+            ui::print_module_header(&i18n::translate("modules.auth"));
+
+            // 加载配置
+            let spinner = ui::create_spinner(&i18n::translate("ui.loading_config"));
+            let cfg = match config::Config::load() {
+                Ok(cfg) => {
+                    ui::spinner_success(&spinner, &i18n::translate("ui.config_loaded"));
+                    cfg
+                }
+                Err(e) => {
+                    ui::spinner_error(
+                        &spinner,
+                        &i18n::translate_args("ui.config_failed", &[("error", &e.to_string())]),
+                    );
+                    return Err(e);
+                }
+            };
+            let account = cfg.smtp.username.clone();
+
+            match action {
+                AuthAction::Login { device, browser } => {
+                    let flow = if *device {
+                        Some(AuthFlow::Device)
+                    } else if *browser {
+                        Some(AuthFlow::Loopback)
+                    } else {
+                        None
+                    };
+
+                    oauth::login(&cfg, flow).await?;
+                    ui::print_success(&i18n::translate_args(
+                        "commands.auth.logged_in",
+                        &[("account", &account)],
+                    ));
+                    if cfg.smtp.auth_type != "xoauth2" {
+                        ui::print_warning(&i18n::translate("commands.auth.enable_hint"));
+                    }
+                }
+                AuthAction::Status => {
+                    let store = oauth::TokenStore::load()?;
+                    match store.get(&account) {
+                        Some(token) => {
+                            ui::print_success(&i18n::translate_args(
+                                "commands.auth.stored",
+                                &[("account", &account)],
+                            ));
+                            if token.has_valid_access_token()
+                                && let Some(expires_at) = token.expires_at
+                                && let Some(expires_at) =
+                                    chrono::DateTime::from_timestamp(expires_at, 0)
+                            {
+                                ui::print_info(&i18n::translate_args(
+                                    "commands.auth.access_expires",
+                                    &[(
+                                        "time",
+                                        &expires_at
+                                            .with_timezone(&chrono::Local)
+                                            .format("%Y-%m-%d %H:%M:%S")
+                                            .to_string(),
+                                    )],
+                                ));
+                            }
+                        }
+                        None => ui::print_warning(&i18n::translate_args(
+                            "commands.auth.not_stored",
+                            &[("account", &account)],
+                        )),
+                    }
+                }
+                AuthAction::Logout => {
+                    let mut store = oauth::TokenStore::load()?;
+                    if store.remove(&account) {
+                        store.save()?;
+                        ui::print_success(&i18n::translate_args(
+                            "commands.auth.logged_out",
+                            &[("account", &account)],
+                        ));
+                    } else {
+                        ui::print_warning(&i18n::translate_args(
+                            "commands.auth.not_stored",
+                            &[("account", &account)],
+                        ));
+                    }
+                }
+            }
+        }
+        Commands::VerifyAlias { address, label } => {
+            ui::print_module_header(&i18n::translate("modules.verify_alias"));
+
+            // 加载配置
+            let spinner = ui::create_spinner(&i18n::translate("ui.loading_config"));
+            let cfg = match config::Config::load() {
+                Ok(cfg) => {
+                    ui::spinner_success(&spinner, &i18n::translate("ui.config_loaded"));
+                    cfg
+                }
+                Err(e) => {
+                    ui::spinner_error(
+                        &spinner,
+                        &i18n::translate_args("ui.config_failed", &[("error", &e.to_string())]),
+                    );
+                    ui::print_info(&i18n::translate("ui.config_hint"));
+                    return Err(e);
+                }
+            };
+
+            // 离线计算并比较
+            if service::email::verify_alias(&cfg, address, label)? {
+                ui::print_success(&i18n::translate_args(
+                    "commands.verify_alias.matched",
+                    &[("address", address), ("label", label)],
+                ));
+            } else {
+                let expected = format!(
+                    "{}@{}",
+                    service::email::deterministic_prefix(&cfg, label)?,
+                    cfg.email.domain
+                );
+                ui::print_error(&i18n::translate_args(
+                    "commands.verify_alias.mismatched",
+                    &[("address", address), ("label", label)],
+                ));
+                ui::print_result_box(
+                    &i18n::translate("commands.verify_alias.expected"),
+                    &expected,
+                );
+                return Err(anyhow::anyhow!(i18n::translate_args(
+                    "commands.verify_alias.mismatched",
+                    &[("address", address), ("label", label)],
+                )));
+            }
+        }
+        Commands::SetupDomain { subdomain } => {
+            ui::print_module_header(&i18n::translate("modules.setup_domain"));
+
+            // 加载配置
+            let spinner = ui::create_spinner(&i18n::translate("ui.loading_config"));
+            let cfg = match config::Config::load() {
+                Ok(cfg) => {
+                    ui::spinner_success(&spinner, &i18n::translate("ui.config_loaded"));
+                    cfg
+                }
+                Err(e) => {
+                    ui::spinner_error(
+                        &spinner,
+                        &i18n::translate_args("ui.config_failed", &[("error", &e.to_string())]),
+                    );
+                    ui::print_info(&i18n::translate("ui.config_hint"));
+                    return Err(e);
+                }
+            };
+
+            // 配置和命令行中的子域名需要在请求 Cloudflare 之前校验
+            let mut subdomains = Vec::new();
+            for name in service::email::static_subdomains(&cfg)
+                .iter()
+                .chain(subdomain)
+            {
+                match service::email::validate_subdomain(name) {
+                    Ok(name) => subdomains.push(name),
+                    Err(e) => {
+                        ui::print_error(&e.to_string());
+                        return Err(e);
+                    }
+                }
+            }
+            subdomains.sort();
+            subdomains.dedup();
+
+            // 检查主域名的邮件路由状态
+            let spinner = ui::create_spinner(&i18n::translate("commands.setup_domain.checking"));
+            let cf_client = api::cloudflare::CloudflareClient::new(&cfg);
+            let settings = match cf_client.get_routing_settings().await {
+                Ok(settings) => {
+                    ui::spinner_success(
+                        &spinner,
+                        &i18n::translate_args(
+                            "commands.setup_domain.status",
+                            &[("domain", &settings.name), ("status", &settings.status)],
+                        ),
+                    );
+                    settings
+                }
+                Err(e) => {
+                    ui::spinner_error(
+                        &spinner,
+                        &i18n::translate_args(
+                            "commands.setup_domain.check_failed",
+                            &[("error", &e.to_string())],
+                        ),
+                    );
+                    return Err(e);
+                }
+            };
+
+            // 需要启用路由的域名：未启用的主域名、配置中的固定子域名以及命令行指定的子域名
+            let mut targets = Vec::new();
+            if !settings.enabled {
+                targets.push(cfg.email.domain.to_lowercase());
+            }
+            targets.extend(
+                subdomains
+                    .iter()
+                    .map(|s| format!("{}.{}", s, cfg.email.domain.to_lowercase())),
+            );
+
+            if let Some(template) = &cfg.email.subdomain_template
+                && template.contains('{')
+            {
+                ui::print_warning(&i18n::translate_args(
+                    "commands.setup_domain.dynamic_template",
+                    &[("template", template)],
+                ));
+            }
+
+            if targets.is_empty() {
+                ui::print_success(&i18n::translate("commands.setup_domain.nothing_to_do"));
+                return Ok(());
+            }
+
+            let mut failed = 0;
+            for name in &targets {
+                let spinner = ui::create_spinner(&i18n::translate_args(
+                    "commands.setup_domain.enabling",
+                    &[("domain", name)],
+                ));
+                match cf_client.enable_routing_dns(name).await {
+                    Ok(_) => ui::spinner_success(
+                        &spinner,
+                        &i18n::translate_args("commands.setup_domain.enabled", &[("domain", name)]),
+                    ),
+                    Err(e) => {
+                        failed += 1;
+                        ui::spinner_error(
+                            &spinner,
+                            &i18n::translate_args(
+                                "commands.setup_domain.enable_failed",
+                                &[("domain", name), ("error", &e.to_string())],
+                            ),
+                        );
+                    }
+                }
+            }
+
+            if failed > 0 {
+                return Err(anyhow::anyhow!(i18n::translate_args(
+                    "commands.setup_domain.summary_failed",
+                    &[
+                        ("failed", &failed.to_string()),
+                        ("total", &targets.len().to_string())
+                    ],
+                )));
+            }
+        }
+        Commands::Signup {
+            label,
+            site,
+            subdomain,
+            from,
+            folders,
+            timeout,
+            poll_interval,
+            after,
+            post,
+        } => {
+            ui::print_module_header(&i18n::translate("modules.signup"));
+
+            // 加载配置
+            let spinner = ui::create_spinner(&i18n::translate("ui.loading_config"));
+            let cfg = match config::Config::load() {
+                Ok(cfg) => {
+                    ui::spinner_success(&spinner, &i18n::translate("ui.config_loaded"));
+                    cfg
+                }
+                Err(e) => {
+                    ui::spinner_error(
+                        &spinner,
+                        &i18n::translate_args("ui.config_failed", &[("error", &e.to_string())]),
+                    );
+                    ui::print_info(&i18n::translate("ui.config_hint"));
+                    return Err(e);
+                }
+            };
+
+            // 生成别名并创建路由
+            let alias_options = service::email::AliasOptions {
+                label: label.clone(),
+                site: site.clone(),
+                subdomain: subdomain.clone(),
+                ..Default::default()
+            };
+            let email_alias = create_alias(&cfg, &alias_options).await?;
+
+            // 监听发往新别名的验证码
+            ui::print_info(&i18n::translate_args(
+                "ui.watching_alias",
+                &[("alias", &email_alias)],
+            ));
+            let options = MonitorOptions {
+                from_filter: from.clone(),
+                to_filter: Some(email_alias.clone()),
+                timeout: *timeout,
+                poll_interval: *poll_interval,
+                mark_seen: post.mark_seen(),
+                action: post.action(),
+                folders: folders.clone(),
+                ..MonitorOptions::default()
+            };
+            let watch_result = watch_code(&cfg, options).await;
+
+            // 无论是否收到验证码，都按要求清理一次性别名
+            if *after != AfterSignupArg::Keep {
+                let cf_client = api::cloudflare::CloudflareClient::new(&cfg);
+                let spinner = ui::create_spinner(&i18n::translate_args(
+                    "ui.signup_cleaning",
+                    &[("alias", &email_alias)],
+                ));
+                let (result, done_key) = match after {
+                    AfterSignupArg::Delete => (
+                        cf_client.delete_email_route(&email_alias).await,
+                        "ui.signup_deleted",
+                    ),
+                    _ => (
+                        cf_client.disable_email_route(&email_alias).await,
+                        "ui.signup_disabled",
+                    ),
+                };
+                match result {
+                    Ok(_) => ui::spinner_success(
+                        &spinner,
+                        &i18n::translate_args(done_key, &[("alias", &email_alias)]),
+                    ),
+                    Err(e) => ui::spinner_error(
+                        &spinner,
+                        &i18n::translate_args(
+                            "ui.signup_cleanup_failed",
+                            &[("error", &e.to_string())],
+                        ),
+                    ),
+                }
+            }
+
+            watch_result?;
+        }
+        Commands::Apply { file, yes } => {
+            ui::print_module_header(&i18n::translate("modules.apply"));
+
+            // 加载配置
+            let spinner = ui::create_spinner(&i18n::translate("ui.loading_config"));
+            let cfg = match config::Config::load() {
+                Ok(cfg) => {
+                    ui::spinner_success(&spinner, &i18n::translate("ui.config_loaded"));
+                    cfg
+                }
+                Err(e) => {
+                    ui::spinner_error(
+                        &spinner,
+                        &i18n::translate_args("ui.config_failed", &[("error", &e.to_string())]),
+                    );
+                    ui::print_info(&i18n::translate("ui.config_hint"));
+                    return Err(e);
+                }
+            };
+
+            // 读取别名清单
+            let spinner = ui::create_spinner(&i18n::translate("commands.apply.loading_manifest"));
+            let desired = match service::sync::load_manifest(file)
+                .and_then(|manifest| service::sync::desired_state(&cfg, &manifest))
+            {
+                Ok(desired) => {
+                    ui::spinner_success(
+                        &spinner,
+                        &i18n::translate_args(
+                            "commands.apply.manifest_loaded",
+                            &[("count", &desired.len().to_string())],
+                        ),
+                    );
+                    desired
+                }
+                Err(e) => {
+                    ui::spinner_error(
+                        &spinner,
+                        &i18n::translate_args(
+                            "commands.apply.manifest_failed",
+                            &[("error", &format!("{:#}", e))],
+                        ),
+                    );
+                    return Err(e);
+                }
+            };
+
+            // 获取现有路由规则并生成计划
+            let spinner = ui::create_spinner(&i18n::translate("ui.fetching_aliases"));
+            let cf_client = api::cloudflare::CloudflareClient::new(&cfg);
+            let routes = match cf_client.list_routes().await {
+                Ok(routes) => {
+                    ui::spinner_success(
+                        &spinner,
+                        &i18n::translate_args(
+                            "ui.aliases_fetched",
+                            &[("count", &routes.len().to_string())],
+                        ),
+                    );
+                    routes
+                }
+                Err(e) => {
+                    ui::spinner_error(
+                        &spinner,
+                        &i18n::translate_args("ui.aliases_failed", &[("error", &e.to_string())]),
+                    );
+                    return Err(e);
+                }
+            };
+
+            let plan = service::sync::plan(&desired, &routes);
+
+            for address in &plan.unmanaged {
+                ui::print_warning(&i18n::translate_args(
+                    "commands.apply.unmanaged",
+                    &[("address", address)],
+                ));
+            }
+
+            if plan.is_empty() {
+                ui::print_success(&i18n::translate("commands.apply.no_changes"));
+                return Ok(());
+            }
+
+            ui::print_sync_plan(&plan);
+
+            // 确认执行
+            if !*yes {
+                println!();
+                ui::print_warning(&i18n::translate("commands.apply.confirm"));
+                print!("{}", i18n::translate("commands.apply.prompt"));
+                io::stdout().flush()?;
+
+                let mut confirm = String::new();
+                io::stdin().lock().read_line(&mut confirm)?;
+
+                if !confirm.trim().eq_ignore_ascii_case("y") {
+                    ui::print_info(&i18n::translate("commands.apply.cancelled"));
+                    return Ok(());
+                }
+            }
+
+            // 执行变更
+            let spinner = ui::create_spinner(&i18n::translate("commands.apply.applying"));
+            let total = plan.changes.len();
+            let mut failures = Vec::new();
+
+            for change in &plan.changes {
+                let (address, result) = match change {
+                    service::sync::Change::Create(state) => (
+                        &state.address,
+                        cf_client.create_route(&state.to_rule()).await,
+                    ),
+                    service::sync::Change::Update { id, after, .. } => (
+                        &after.address,
+                        cf_client.update_route(id, &after.to_rule()).await,
+                    ),
+                    service::sync::Change::Delete { id, state } => {
+                        (&state.address, cf_client.delete_route(id).await)
+                    }
+                };
+
+                if let Err(e) = result {
+                    failures.push((address.clone(), e.to_string()));
+                }
+            }
+
+            let success = (total - failures.len()).to_string();
+            let total = total.to_string();
+            if failures.is_empty() {
+                ui::spinner_success(
+                    &spinner,
+                    &i18n::translate_args(
+                        "commands.apply.apply_success",
+                        &[("success", &success), ("total", &total)],
+                    ),
+                );
+            } else {
+                ui::spinner_error(
+                    &spinner,
+                    &i18n::translate_args(
+                        "commands.apply.apply_partial",
+                        &[("success", &success), ("total", &total)],
+                    ),
+                );
+                for (address, error) in &failures {
+                    ui::print_error(&format!("{}: {}", address.yellow(), error));
+                }
+                return Err(anyhow::anyhow!(i18n::translate_args(
+                    "commands.apply.summary_failed",
+                    &[("failed", &failures.len().to_string()), ("total", &total)],
+                )));
+            }
+        }
+        Commands::Init => {
+            ui::print_module_header(&i18n::translate("modules.init"));
+
+            // 初始化配置文件
+            let spinner = ui::create_spinner(&i18n::translate("ui.initializing_config"));
+            match config::Config::init() {
+                Ok(path) => {
+                    ui::spinner_success(&spinner, &i18n::translate("ui.config_initialized"));
+                    // 显示结果
+                    ui::print_result_box(
+                        &i18n::translate("ui.config_location"),
+                        &path.to_string_lossy(),
+                    );
+                    ui::print_info(&i18n::translate("ui.config_edit_hint"));
+                }
+                Err(e) => {
+                    ui::spinner_error(
+                        &spinner,
+                        &i18n::translate_args(
+                            "ui.config_init_failed",
+                            &[("error", &e.to_string())],
+                        ),
+                    );
+                    return Err(e);
+                }
+            };
+        }
+    }
+
+    Ok(())
+}
+
+/// 等待验证码邮件，找到后复制到剪贴板并显示
+async fn watch_code(cfg: &config::Config, options: MonitorOptions) -> Result<CodeResult> {
+    // 创建邮件监听器
+    let monitor = MailMonitor::new(cfg, options);
+
+    // 等待验证码
+    let spinner = ui::create_spinner(&i18n::translate("ui.waiting_code"));
+    match monitor.wait_for_code().await {
+        Ok(result) => {
+            ui::spinner_success(&spinner, &i18n::translate("ui.code_found"));
+
+            // 置信度低的结果只显示，不自动复制
+            let confidence = format!("{:.0}", result.confidence * 100.0);
+            if result.confidence < service::extractor::LOW_CONFIDENCE {
+                ui::print_warning(&i18n::translate_args(
+                    "ui.code_low_confidence",
+                    &[("confidence", &confidence)],
+                ));
+            } else if let Err(e) = util::clipboard::copy_to_clipboard(&result.code) {
+                ui::print_error(&i18n::translate_args(
+                    "ui.clipboard_failed",
+                    &[("error", &e.to_string())],
+                ));
+            } else {
+                ui::print_success(&i18n::translate("ui.code_copied"));
+            }
+
+            // 显示最终结果
+            ui::print_info(&i18n::translate_args(
+                "ui.code_confidence",
+                &[("confidence", &confidence)],
+            ));
+            ui::print_result_box(&i18n::translate("ui.code_result"), &result.code);
+            Ok(result)
+        }
+        Err(e) => {
+            ui::spinner_error(
+                &spinner,
+                &i18n::translate_args("ui.code_failed", &[("error", &e.to_string())]),
+            );
+            Err(e)
+        }
+    }
+}
+
+/// 生成邮箱别名并创建路由，成功后复制到剪贴板并显示
+async fn create_alias(
+    cfg: &config::Config,
+    alias_options: &service::email::AliasOptions,
+) -> Result<String> {
+    // 先检查子域名和别名格式，无效时不必请求 Cloudflare
+    if let Err(e) = service::email::validate_options(cfg, alias_options) {
+        ui::print_error(&i18n::translate_args(
+            "ui.alias_failed",
+            &[("error", &e.to_string())],
+        ));
+        return Err(e);
+    }
+
+    // 获取已有别名，避免生成重复的别名
+    let spinner = ui::create_spinner(&i18n::translate("ui.fetching_aliases"));
+    let cf_client = api::cloudflare::CloudflareClient::new(cfg);
+    let existing = match cf_client.list_email_routes().await {
+        Ok(aliases) => {
+            ui::spinner_success(
+                &spinner,
+                &i18n::translate_args(
+                    "ui.aliases_fetched",
+                    &[("count", &aliases.len().to_string())],
+                ),
+            );
+            aliases
+        }
+        Err(e) => {
+            ui::spinner_error(
+                &spinner,
+                &i18n::translate_args("ui.aliases_failed", &[("error", &e.to_string())]),
+            );
+            return Err(e);
+        }
+    };
+
+    // 生成邮箱别名
+    let spinner = ui::create_spinner(&i18n::translate("ui.generating_alias"));
+    let email_alias = match service::email::generate_alias(cfg, alias_options, &existing) {
+        Ok(alias) => {
+            ui::spinner_success(
+                &spinner,
+                &i18n::translate_args("ui.alias_generated", &[("alias", &alias)]),
+            );
+            alias
+        }
+        Err(e) => {
+            ui::spinner_error(
+                &spinner,
+                &i18n::translate_args("ui.alias_failed", &[("error", &e.to_string())]),
+            );
+            return Err(e);
+        }
+    };
+
+    // 报告随机前缀的熵，帮助选择安全的 random_length
+    if cfg.alias.prefix_mode == "random"
+        && alias_options.prefix.is_none()
+        && alias_options.site.is_none()
+        && !alias_options.subaddress
+    {
+        let bits = service::email::random_entropy_bits(cfg, cfg.alias.random_length);
+        let bits_str = format!("{:.1}", bits);
+        ui::print_info(&i18n::translate_args(
+            "ui.alias_entropy",
+            &[("bits", &bits_str)],
+        ));
+        if bits < service::email::RECOMMENDED_ENTROPY_BITS {
+            ui::print_warning(&i18n::translate_args(
+                "ui.alias_entropy_low",
+                &[
+                    ("bits", &bits_str),
+                    (
+                        "recommended",
+                        &service::email::RECOMMENDED_ENTROPY_BITS.to_string(),
+                    ),
+                ],
+            ));
+        }
+    }
+
+    // 创建Cloudflare邮件路由（子地址由基础别名的路由接收）
+    if alias_options.subaddress {
+        ui::print_info(&i18n::translate("ui.subaddress_no_route"));
+    } else {
+        let spinner = ui::create_spinner(&i18n::translate("ui.configuring_route"));
+        match cf_client.create_email_route(&email_alias).await {
+            Ok(_) => {
+                ui::spinner_success(&spinner, &i18n::translate("ui.route_configured"));
+            }
+            Err(e) => {
+                ui::spinner_error(
+                    &spinner,
+                    &i18n::translate_args("ui.route_failed", &[("error", &e.to_string())]),
+                );
+                return Err(e);
+            }
+        }
+    }
+
+    // 复制到剪贴板
+    if let Err(e) = util::clipboard::copy_to_clipboard(&email_alias) {
+        ui::print_error(&i18n::translate_args(
+            "ui.clipboard_failed",
+            &[("error", &e.to_string())],
+        ));
+    } else {
+        ui::print_success(&i18n::translate("ui.clipboard_success"));
+    }
+
+    // 显示最终结果
+    ui::print_result_box(&i18n::translate("ui.result_title"), &email_alias);
+    ui::print_info(&i18n::translate("ui.alias_ready"));
+
+    Ok(email_alias)
 }
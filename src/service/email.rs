@@ -1,65 +1,1599 @@
 use crate::config::Config;
+use crate::util::i18n::translate_args;
 use anyhow::{Result, anyhow};
-use rand::{Rng, distributions::Alphanumeric, distributions::Distribution};
-use std::iter;
-
-pub fn generate_alias(config: &Config, custom_prefix: Option<String>) -> Result<String> {
-    let prefix = match custom_prefix {
-        Some(prefix) => prefix,
-        None => match config.alias.prefix_mode.as_str() {
-            "random" => generate_random_prefix(config),
-            "custom" => {
-                if config.alias.custom_prefixes.is_empty() {
-                    return Err(anyhow!("配置错误: 选择了自定义前缀模式，但未提供任何前缀"));
+use chrono::Local;
+use chrono::format::{Item, StrftimeItems};
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, distributions::Distribution};
+use regex::Regex;
+use sha2::Sha256;
+use std::collections::HashSet;
+
+/// 生成唯一别名时的最大尝试次数
+const MAX_ATTEMPTS: usize = 20;
+
+/// 别名生成选项
+#[derive(Debug, Default)]
+pub struct AliasOptions {
+    /// 自定义前缀
+    pub prefix: Option<String>,
+    /// 服务标签，用于模板中的 {label}
+    pub label: Option<String>,
+    /// 网站地址或服务名称，用于派生前缀
+    pub site: Option<String>,
+    /// 是否生成子地址（base+tag）
+    pub subaddress: bool,
+    /// 子地址使用的基础别名
+    pub base: Option<String>,
+    /// 别名所在的子域名（覆盖配置中的 subdomain_template）
+    pub subdomain: Option<String>,
+}
+
+/// 生成邮箱别名
+///
+/// `existing` 为Cloudflare中已存在的别名，生成结果保证不与其重复。
+pub fn generate_alias(
+    config: &Config,
+    options: &AliasOptions,
+    existing: &[String],
+) -> Result<String> {
+    let taken: HashSet<String> = existing.iter().map(|alias| alias.to_lowercase()).collect();
+    let is_free = |alias: &str| !taken.contains(&alias.to_lowercase());
+
+    if options.subaddress {
+        return generate_subaddress(config, options, &taken);
+    }
+
+    // 别名所在的域名（可能是子域名）
+    let domain = alias_domain(config, options)?;
+    let build = |prefix: &str| validate_alias(config, &format!("{}@{}", prefix, domain));
+
+    // 确定性的别名不做替换，直接报告冲突
+    let take = |alias: Result<String>| {
+        let alias = alias?;
+        if is_free(&alias) {
+            Ok(alias)
+        } else {
+            Err(anyhow!("别名已存在: {}", alias))
+        }
+    };
+
+    if let Some(prefix) = &options.prefix {
+        return take(build(prefix));
+    }
+
+    // 根据网站地址或服务名称派生前缀
+    if let Some(site) = &options.site {
+        let base = site_prefix(site)?;
+        let length = config.alias.site_suffix_length;
+
+        return match config.alias.site_suffix.as_str() {
+            "none" => take(build(&base)),
+            "hmac" => {
+                let token = hmac_token(&alias_secret(config)?, &base, length);
+                take(build(&format!("{}.{}", base, token)))
+            }
+            "random" => {
+                for _ in 0..MAX_ATTEMPTS {
+                    let alias = build(&format!("{}.{}", base, random_suffix(length)))?;
+                    if is_free(&alias) {
+                        return Ok(alias);
+                    }
+                }
+                Err(anyhow!(
+                    "在 {} 次尝试后仍未生成唯一的别名，请增大 site_suffix_length",
+                    MAX_ATTEMPTS
+                ))
+            }
+            other => Err(anyhow!("配置错误: 不支持的网站后缀模式: {}", other)),
+        };
+    }
+
+    match config.alias.prefix_mode.as_str() {
+        "random" => {
+            for _ in 0..MAX_ATTEMPTS {
+                let alias = build(&generate_random_prefix(config, config.alias.random_length))?;
+                if is_free(&alias) {
+                    return Ok(alias);
+                }
+            }
+            Err(anyhow!(
+                "在 {} 次尝试后仍未生成唯一的别名，请增大 random_length",
+                MAX_ATTEMPTS
+            ))
+        }
+        "custom" => {
+            if config.alias.custom_prefixes.is_empty() {
+                return Err(anyhow!("配置错误: 选择了自定义前缀模式，但未提供任何前缀"));
+            }
+            let available: Vec<String> = config
+                .alias
+                .custom_prefixes
+                .iter()
+                .map(|prefix| build(prefix))
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .filter(|alias| is_free(alias))
+                .collect();
+
+            available.choose(&mut OsRng).cloned().ok_or_else(|| {
+                anyhow!(
+                    "自定义前缀已全部被使用 ({} 个)，请在 custom_prefixes 中添加新的前缀",
+                    config.alias.custom_prefixes.len()
+                )
+            })
+        }
+        "template" => {
+            let template = config
+                .alias
+                .template
+                .as_deref()
+                .ok_or_else(|| anyhow!("配置错误: 选择了模板模式，但未提供 template"))?;
+
+            for _ in 0..MAX_ATTEMPTS {
+                let alias = build(&render_template(config, template, options)?)?;
+                if is_free(&alias) {
+                    return Ok(alias);
                 }
-                let mut rng = rand::thread_rng();
-                let idx = rng.gen_range(0..config.alias.custom_prefixes.len());
-                config.alias.custom_prefixes[idx].clone()
             }
-            _ => {
-                return Err(anyhow!(
-                    "配置错误: 不支持的前缀模式: {}",
-                    config.alias.prefix_mode
-                ));
+            Err(anyhow!(
+                "在 {} 次尝试后仍未生成唯一的别名，请在模板中加入 {{rand}} 占位符",
+                MAX_ATTEMPTS
+            ))
+        }
+        "deterministic" => {
+            let label = options
+                .label
+                .as_deref()
+                .ok_or_else(|| anyhow!("确定性模式需要通过 --label 指定服务标签"))?;
+            take(build(&deterministic_prefix(config, label)?))
+        }
+        _ => Err(anyhow!(
+            "配置错误: 不支持的前缀模式: {}",
+            config.alias.prefix_mode
+        )),
+    }
+}
+
+/// 在请求 Cloudflare 之前检查生成选项
+///
+/// 按选项生成一次候选别名，子域名、前缀或模板无效时尽早报错；
+/// 别名是否重复、子地址的基础别名是否有路由需要在获取路由列表后检查。
+pub fn validate_options(config: &Config, options: &AliasOptions) -> Result<()> {
+    if options.subaddress {
+        return build_subaddress(config, options).map(|_| ());
+    }
+    generate_alias(config, options, &[]).map(|_| ())
+}
+
+/// 基于已有路由生成子地址，如 base+tag@domain
+///
+/// 子地址由基础别名的路由规则接收，不需要创建新的路由。
+fn generate_subaddress(
+    config: &Config,
+    options: &AliasOptions,
+    taken: &HashSet<String>,
+) -> Result<String> {
+    let (base, alias) = build_subaddress(config, options)?;
+    if !taken.contains(&base) {
+        return Err(anyhow!("基础别名 {} 没有对应的路由规则", base));
+    }
+    Ok(alias)
+}
+
+/// 生成子地址，返回 (基础别名, 子地址)
+fn build_subaddress(config: &Config, options: &AliasOptions) -> Result<(String, String)> {
+    let base = options
+        .base
+        .as_deref()
+        .or(config.alias.subaddress_base.as_deref())
+        .ok_or_else(|| {
+            anyhow!("子地址模式需要通过 --base 或配置项 subaddress_base 指定基础别名")
+        })?;
+    let base = if base.contains('@') {
+        validate_alias(config, base)?
+    } else {
+        format_alias(config, base)?
+    };
+
+    let separator = &config.alias.subaddress_separator;
+    let (base_local, domain) = base.split_once('@').unwrap_or((&base, ""));
+    if base_local.contains(separator.as_str()) {
+        return Err(anyhow!("基础别名 {} 已经是子地址", base));
+    }
+
+    let tag = match (&options.site, &options.label) {
+        (Some(site), _) => site_prefix(site)?,
+        (None, Some(label)) => slugify(label),
+        (None, None) => random_suffix(config.alias.site_suffix_length),
+    };
+    if tag.is_empty() {
+        return Err(anyhow!("子地址标签不能为空"));
+    }
+
+    let alias = validate_alias(
+        config,
+        &format!("{}{}{}@{}", base_local, separator, tag, domain),
+    )?;
+    Ok((base, alias))
+}
+
+/// 计算别名所在的域名
+///
+/// 优先使用 `--subdomain`，其次渲染配置中的 `subdomain_template`，否则使用主域名。
+fn alias_domain(config: &Config, options: &AliasOptions) -> Result<String> {
+    let subdomain = match (&options.subdomain, &config.email.subdomain_template) {
+        (Some(subdomain), _) => validate_subdomain(subdomain)?,
+        (None, Some(template)) => validate_subdomain(&render_template(config, template, options)?)?,
+        (None, None) => return Ok(config.email.domain.to_lowercase()),
+    };
+
+    Ok(format!(
+        "{}.{}",
+        subdomain,
+        config.email.domain.to_lowercase()
+    ))
+}
+
+/// 校验子域名的每个DNS标签，返回统一为小写的子域名
+pub fn validate_subdomain(subdomain: &str) -> Result<String> {
+    let subdomain = subdomain.trim().to_lowercase();
+    if !subdomain.split('.').all(is_valid_dns_label) {
+        return Err(anyhow!(translate_args(
+            "errors.invalid_subdomain",
+            &[("subdomain", &subdomain)]
+        )));
+    }
+    Ok(subdomain)
+}
+
+/// 检查单个DNS标签是否合法
+fn is_valid_dns_label(label: &str) -> bool {
+    (1..=63).contains(&label.len())
+        && label
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+}
+
+/// 配置中需要启用邮件路由的固定子域名
+///
+/// 包括 `subdomains` 列表，以及不含占位符的 `subdomain_template`。
+pub fn static_subdomains(config: &Config) -> Vec<String> {
+    let mut subdomains: Vec<String> = config
+        .email
+        .subdomains
+        .iter()
+        .map(|subdomain| subdomain.trim().to_lowercase())
+        .collect();
+
+    if let Some(template) = &config.email.subdomain_template
+        && !template.contains('{')
+    {
+        subdomains.push(template.trim().to_lowercase());
+    }
+
+    subdomains.sort();
+    subdomains.dedup();
+    subdomains
+}
+
+/// 判断域名是否属于已配置的域名
+///
+/// 主域名和 `subdomains` 中的子域名始终有效；
+/// 配置了带占位符的 `subdomain_template` 时，视为已启用通配子域名。
+pub fn is_configured_domain(config: &Config, domain: &str) -> bool {
+    let domain = domain.to_lowercase();
+    let root = config.email.domain.to_lowercase();
+
+    if domain == root {
+        return true;
+    }
+
+    let Some(subdomain) = domain.strip_suffix(&format!(".{}", root)) else {
+        return false;
+    };
+
+    let wildcard = config
+        .email
+        .subdomain_template
+        .as_ref()
+        .is_some_and(|template| template.contains('{'));
+
+    static_subdomains(config).iter().any(|s| s == subdomain)
+        || (wildcard && subdomain.split('.').all(is_valid_dns_label))
+}
+
+/// 判断收件地址是否匹配过滤条件
+///
+/// 过滤条件为基础别名时，同时匹配它的所有子地址；
+/// 过滤条件本身是子地址时只做精确匹配。
+pub fn recipient_matches(filter: &str, recipient: &str, separator: &str) -> bool {
+    let filter = filter.trim().to_lowercase();
+    let recipient = recipient.trim().to_lowercase();
+
+    if filter == recipient {
+        return true;
+    }
+
+    let (Some((filter_local, filter_domain)), Some((local, domain))) =
+        (filter.rsplit_once('@'), recipient.rsplit_once('@'))
+    else {
+        return false;
+    };
+
+    if separator.is_empty() || filter_domain != domain || filter_local.contains(separator) {
+        return false;
+    }
+
+    local
+        .split_once(separator)
+        .is_some_and(|(base, _)| base == filter_local)
+}
+
+/// 渲染别名模板
+///
+/// 支持的占位符：
+/// - `{label}`：通过 `--label` 指定的服务标签
+/// - `{rand}` / `{rand:N}`：N 位随机字符（默认 random_length 位）；
+///   words 字符集按 word_count 生成单词，不能指定长度，`{rand:N}` 会报错
+/// - `{date}` / `{date:FMT}`：当前日期，FMT 为 strftime 格式（默认 `%Y%m%d`）
+/// - `{user}`：当前系统用户名
+/// - `{word}`：内置词表中的随机单词
+pub fn render_template(config: &Config, template: &str, options: &AliasOptions) -> Result<String> {
+    static PLACEHOLDER: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"\{([a-z]+)(?::([^}]*))?\}").unwrap());
+
+    let mut rendered = String::with_capacity(template.len());
+    let mut last = 0;
+
+    for caps in PLACEHOLDER.captures_iter(template) {
+        let whole = caps.get(0).unwrap();
+        rendered.push_str(&template[last..whole.start()]);
+        last = whole.end();
+
+        let arg = caps.get(2).map(|m| m.as_str());
+        let value = match &caps[1] {
+            "label" => options
+                .label
+                .as_deref()
+                .map(slugify)
+                .filter(|label| !label.is_empty())
+                .ok_or_else(|| anyhow!("模板中使用了 {{label}}，请通过 --label 指定服务标签"))?,
+            "rand" => {
+                let length = match arg {
+                    Some(_) if config.alias.random_charset == "words" => {
+                        return Err(anyhow!(
+                            "模板错误: words 字符集不支持指定长度的 {{rand:N}}，请使用 {{rand}} 或其他字符集"
+                        ));
+                    }
+                    Some(n) => n
+                        .parse()
+                        .map_err(|_| anyhow!("模板错误: 无效的随机长度: {}", n))?,
+                    None => config.alias.random_length,
+                };
+                generate_random_prefix(config, length)
+            }
+            "date" => format_date(arg.unwrap_or("%Y%m%d"))?,
+            "user" => current_user().ok_or_else(|| anyhow!("模板错误: 无法获取当前系统用户名"))?,
+            "word" => random_word().to_string(),
+            other => return Err(anyhow!("模板错误: 不支持的占位符: {{{}}}", other)),
+        };
+        rendered.push_str(&value);
+    }
+    rendered.push_str(&template[last..]);
+
+    Ok(rendered)
+}
+
+/// 按 strftime 格式格式化当前日期，格式无效时返回错误而不是 panic
+fn format_date(format: &str) -> Result<String> {
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return Err(anyhow!("模板错误: 无效的日期格式: {}", format));
+    }
+    Ok(Local::now()
+        .format_with_items(items.into_iter())
+        .to_string())
+}
+
+/// 将任意文本转换为适合作为别名的形式（小写字母、数字和连字符）
+pub fn slugify(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// 常见的多级公共后缀，派生前缀时与顶级域名一并去除
+const MULTI_LABEL_SUFFIXES: &[&str] = &[
+    "co.uk", "org.uk", "ac.uk", "gov.uk", "com.cn", "net.cn", "org.cn", "gov.cn", "com.hk",
+    "com.tw", "com.au", "net.au", "co.jp", "co.kr", "co.nz", "co.in", "com.br", "com.sg",
+];
+
+/// 获取主机名的主域名，`mail.example.co.uk` 得到 `example.co.uk`
+pub fn registrable_domain(host: &str) -> String {
+    let host = host.trim_end_matches('.').to_lowercase();
+    if host.parse::<std::net::IpAddr>().is_ok() {
+        return host;
+    }
+
+    let labels: Vec<&str> = host.split('.').collect();
+    let keep = if labels.len() > 2
+        && MULTI_LABEL_SUFFIXES.contains(&labels[labels.len() - 2..].join(".").as_str())
+    {
+        3
+    } else {
+        2
+    };
+    labels[labels.len().saturating_sub(keep)..].join(".")
+}
+
+/// 从网址或服务名称派生别名前缀
+///
+/// `https://shop.example.com/signup` 得到 `shop-example`，`Stripe` 得到 `stripe`。
+pub fn site_prefix(site: &str) -> Result<String> {
+    let site = site.trim();
+
+    let host = if site.contains("://") {
+        reqwest::Url::parse(site).ok()
+    } else if site.contains('.') || site.contains('/') {
+        reqwest::Url::parse(&format!("https://{}", site)).ok()
+    } else {
+        None
+    }
+    .and_then(|url| url.host_str().map(|host| host.to_lowercase()));
+
+    let prefix = match host {
+        Some(host) => {
+            let host = host.trim_end_matches('.');
+            let host = host.strip_prefix("www.").unwrap_or(host);
+            // 去除公共后缀（主域名中第一个标签之后的部分）
+            let domain = registrable_domain(host);
+            let name = match domain.split_once('.') {
+                Some((_, suffix)) if host.parse::<std::net::IpAddr>().is_err() => host
+                    .strip_suffix(suffix)
+                    .map(|name| name.trim_end_matches('.'))
+                    .unwrap_or(host),
+                _ => host,
+            };
+            slugify(name)
+        }
+        None => slugify(site),
+    };
+
+    if prefix.is_empty() {
+        return Err(anyhow!("无法从 {} 派生别名前缀", site));
+    }
+    Ok(prefix)
+}
+
+/// 根据密钥和服务标签计算确定性前缀
+///
+/// 拥有相同密钥的人可以离线重新计算出同一个别名。
+pub fn deterministic_prefix(config: &Config, label: &str) -> Result<String> {
+    let label = slugify(label);
+    if label.is_empty() {
+        return Err(anyhow!("服务标签不能为空"));
+    }
+
+    let secret = alias_secret(config)?;
+    Ok(hmac_token(
+        &secret,
+        &format!("alias:{}", label),
+        config.alias.deterministic_length,
+    ))
+}
+
+/// 验证别名是否由指定服务标签派生
+///
+/// `address` 可以是完整地址，也可以只包含本地部分。
+pub fn verify_alias(config: &Config, address: &str, label: &str) -> Result<bool> {
+    let expected = deterministic_prefix(config, label)?;
+    let address = address.trim().to_lowercase();
+
+    let local = match address.rsplit_once('@') {
+        Some((local, domain)) => {
+            if !is_configured_domain(config, domain) {
+                return Ok(false);
             }
-        },
+            local
+        }
+        None => address.as_str(),
+    };
+
+    Ok(local == expected)
+}
+
+/// 获取用于派生别名的密钥，环境变量 CFMAIL_ALIAS_SECRET 优先于配置文件
+fn alias_secret(config: &Config) -> Result<String> {
+    std::env::var("CFMAIL_ALIAS_SECRET")
+        .ok()
+        .or_else(|| config.alias.secret.clone())
+        .filter(|secret| !secret.is_empty())
+        .ok_or_else(|| anyhow!("配置错误: 未设置 alias.secret 或环境变量 CFMAIL_ALIAS_SECRET"))
+}
+
+/// HMAC-SHA256 的 256 位摘要最多可以生成的 base32 字符数
+pub const MAX_HMAC_TOKEN_LENGTH: usize = 256 / 5;
+
+/// 计算 HMAC-SHA256 并截取为 length 位小写 base32 字符（最多 [`MAX_HMAC_TOKEN_LENGTH`] 位）
+fn hmac_token(secret: &str, message: &str, length: usize) -> String {
+    const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC可以接受任意长度的密钥");
+    mac.update(message.as_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // 每5位映射为一个base32字符
+    let mut token = String::with_capacity(length);
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for byte in digest {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 && token.len() < length {
+            bits -= 5;
+            token.push(char::from(ALPHABET[((buffer >> bits) & 0x1f) as usize]));
+        }
+    }
+    token
+}
+
+/// 生成小写字母和数字组成的随机后缀
+fn random_suffix(length: usize) -> String {
+    const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    let mut rng = OsRng;
+    (0..length)
+        .map(|_| char::from(*CHARS.choose(&mut rng).unwrap()))
+        .collect()
+}
+
+/// 获取当前系统用户名
+fn current_user() -> Option<String> {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .ok()
+        // 环境变量不可用时，退回到用户主目录名
+        .or_else(|| {
+            dirs::home_dir().and_then(|home| {
+                home.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            })
+        })
+        .map(|user| slugify(&user))
+        .filter(|user| !user.is_empty())
+}
+
+/// 内置词表
+static WORDLIST: Lazy<Vec<&'static str>> = Lazy::new(|| {
+    include_str!("../../assets/wordlist.txt")
+        .lines()
+        .map(str::trim)
+        .filter(|word| !word.is_empty())
+        .collect()
+});
+
+/// 从内置词表中随机选取一个单词
+fn random_word() -> &'static str {
+    WORDLIST.choose(&mut OsRng).copied().unwrap_or("word")
+}
+
+/// 拼接前缀与域名，并校验结果
+pub fn format_alias(config: &Config, prefix: &str) -> Result<String> {
+    validate_alias(config, &format!("{}@{}", prefix, config.email.domain))
+}
+
+/// 本地部分允许的最大字节数（RFC 5321）
+const MAX_LOCAL_PART_LEN: usize = 64;
+
+/// 按 RFC 5321 规则校验别名，返回统一为小写的地址
+///
+/// 本地部分只允许 dot-atom 形式，域名必须是已配置的域名或子域名。
+pub fn validate_alias(config: &Config, address: &str) -> Result<String> {
+    let address = address.trim().to_lowercase();
+
+    let (local, domain) = address.rsplit_once('@').ok_or_else(|| {
+        anyhow!(translate_args(
+            "errors.alias_invalid",
+            &[("address", &address)]
+        ))
+    })?;
+
+    if local.is_empty() {
+        return Err(anyhow!(translate_args(
+            "errors.alias_empty_local",
+            &[("address", &address)]
+        )));
+    }
+
+    if local.len() > MAX_LOCAL_PART_LEN {
+        return Err(anyhow!(translate_args(
+            "errors.alias_too_long",
+            &[
+                ("address", &address),
+                ("length", &local.len().to_string()),
+                ("max", &MAX_LOCAL_PART_LEN.to_string()),
+            ]
+        )));
+    }
+
+    // RFC 5322 atext 字符，加上用于分隔的点号
+    if let Some(c) = local
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~.".contains(*c)))
+    {
+        return Err(anyhow!(translate_args(
+            "errors.alias_invalid_char",
+            &[("address", &address), ("char", &c.to_string())]
+        )));
+    }
+
+    if local.starts_with('.') || local.ends_with('.') || local.contains("..") {
+        return Err(anyhow!(translate_args(
+            "errors.alias_invalid_dot",
+            &[("address", &address)]
+        )));
+    }
+
+    if !is_configured_domain(config, domain) {
+        return Err(anyhow!(translate_args(
+            "errors.alias_domain_not_configured",
+            &[("domain", domain), ("expected", &config.email.domain)]
+        )));
+    }
+
+    Ok(address)
+}
+
+/// 推荐的随机前缀最小熵（比特）
+pub const RECOMMENDED_ENTROPY_BITS: f64 = 40.0;
+
+/// 容易混淆的字符
+const AMBIGUOUS_CHARS: &[char] = &['0', 'o', '1', 'l'];
+
+/// 获取字符类字符集的字符池（words 和 pronounceable 返回 None）
+///
+/// 邮箱本地部分实际上不区分大小写，因此只使用小写字母。
+fn charset_pool(config: &Config) -> Option<Vec<char>> {
+    let chars = match config.alias.random_charset.as_str() {
+        "alphabetic" => "abcdefghijklmnopqrstuvwxyz",
+        "numeric" => "0123456789",
+        "words" | "pronounceable" => return None,
+        // alphanumeric 以及未知的字符集默认使用小写字母数字组合
+        _ => "abcdefghijklmnopqrstuvwxyz0123456789",
     };
 
-    Ok(format!("{}@{}", prefix, config.email.domain))
+    Some(filter_ambiguous(config, chars))
 }
 
-fn generate_random_prefix(config: &Config) -> String {
-    let mut rng = rand::thread_rng();
+/// 按配置去除容易混淆的字符
+fn filter_ambiguous(config: &Config, chars: &str) -> Vec<char> {
+    chars
+        .chars()
+        .filter(|c| !(config.alias.exclude_ambiguous && AMBIGUOUS_CHARS.contains(c)))
+        .collect()
+}
+
+/// 估算随机前缀的熵（比特）
+pub fn random_entropy_bits(config: &Config, length: usize) -> f64 {
+    if let Some(pool) = charset_pool(config) {
+        return length as f64 * (pool.len() as f64).log2();
+    }
 
     match config.alias.random_charset.as_str() {
-        "alphanumeric" => iter::repeat(())
-            .map(|()| rng.sample(Alphanumeric))
-            .map(char::from)
-            .take(config.alias.random_length)
-            .collect(),
-        "alphabetic" => {
-            let chars: Vec<char> = "abcdefghijklmnopqrstuvwxyz".chars().collect();
+        "words" => {
+            config.alias.word_count.max(1) as f64 * (WORDLIST.len() as f64).log2()
+                + config.alias.word_digits as f64 * 10f64.log2()
+        }
+        _ => {
+            let (consonants, vowels) = pronounceable_pools(config);
+            (0..length)
+                .map(|i| {
+                    let pool = if i % 2 == 0 { &consonants } else { &vowels };
+                    (pool.len() as f64).log2()
+                })
+                .sum()
+        }
+    }
+}
+
+/// 使用操作系统的安全随机源生成随机前缀
+///
+/// 字符按均匀分布抽取，不存在取模偏差。
+fn generate_random_prefix(config: &Config, length: usize) -> String {
+    let mut rng = OsRng;
+
+    match config.alias.random_charset.as_str() {
+        "words" => generate_word_prefix(config, &mut rng),
+        "pronounceable" => generate_pronounceable_prefix(config, length, &mut rng),
+        _ => {
+            let chars = charset_pool(config).unwrap_or_default();
             let dist = rand::distributions::Uniform::from(0..chars.len());
 
-            (0..config.alias.random_length)
-                .map(|_| chars[dist.sample(&mut rng)])
-                .collect()
+            (0..length).map(|_| chars[dist.sample(&mut rng)]).collect()
         }
-        "numeric" => {
-            let chars: Vec<char> = "0123456789".chars().collect();
-            let dist = rand::distributions::Uniform::from(0..chars.len());
+    }
+}
+
+/// 生成由单词组成的前缀，如 quiet-otter-42
+fn generate_word_prefix(config: &Config, rng: &mut impl Rng) -> String {
+    let mut parts: Vec<String> = (0..config.alias.word_count.max(1))
+        .map(|_| WORDLIST.choose(rng).copied().unwrap_or("word").to_string())
+        .collect();
+
+    if config.alias.word_digits > 0 {
+        let digits: String = (0..config.alias.word_digits)
+            .map(|_| char::from(b'0' + rng.gen_range(0..10)))
+            .collect();
+        parts.push(digits);
+    }
+
+    parts.join(&config.alias.word_separator)
+}
+
+/// 可发音前缀使用的辅音和元音
+fn pronounceable_pools(config: &Config) -> (Vec<char>, Vec<char>) {
+    (
+        filter_ambiguous(config, "bdfghjklmnprstvz"),
+        filter_ambiguous(config, "aeiou"),
+    )
+}
+
+/// 生成辅音、元音交替的可发音前缀，如 kamoleti
+fn generate_pronounceable_prefix(config: &Config, length: usize, rng: &mut impl Rng) -> String {
+    let (consonants, vowels) = pronounceable_pools(config);
+
+    (0..length)
+        .map(|i| {
+            let pool = if i % 2 == 0 { &consonants } else { &vowels };
+            *pool.choose(rng).unwrap()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::i18n::{SupportedLocale, with_locale};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    /// 默认的 `[alias]` 配置
+    const ALIAS: &str = r#"
+prefix_mode = "random"
+custom_prefixes = []
+random_length = 8
+random_charset = "alphabetic"
+"#;
+
+    /// 构造测试配置，`email` 追加到 `[email]` 段，`alias` 为完整的 `[alias]` 段
+    fn config_with(email: &str, alias: &str) -> Config {
+        toml::from_str(&format!(
+            r#"
+[cloudflare]
+api_key = "test"
+api_token = ""
+zone_id = "test"
+email = "test@example.com"
+
+[email]
+domain = "example.com"
+target_email = "me@example.net"
+{}
+
+[alias]
+{}
 
-            (0..config.alias.random_length)
-                .map(|_| chars[dist.sample(&mut rng)])
-                .collect()
+[smtp]
+username = "user"
+password = "pass"
+imap_server = "127.0.0.1"
+imap_port = 993
+smtp_server = "127.0.0.1"
+smtp_port = 587
+"#,
+            email, alias
+        ))
+        .unwrap()
+    }
+
+    fn config() -> Config {
+        config_with("", ALIAS)
+    }
+
+    #[test]
+    fn validate_alias_normalizes_case() {
+        assert_eq!(
+            validate_alias(&config(), " Shop.Alias@Example.COM ").unwrap(),
+            "shop.alias@example.com"
+        );
+    }
+
+    #[test]
+    fn validate_alias_limits_local_part_to_64_bytes() {
+        let config = config();
+        let longest = "a".repeat(MAX_LOCAL_PART_LEN);
+        assert!(validate_alias(&config, &format!("{}@example.com", longest)).is_ok());
+        assert!(validate_alias(&config, &format!("{}b@example.com", longest)).is_err());
+        assert!(validate_alias(&config, "@example.com").is_err());
+        assert!(validate_alias(&config, "example.com").is_err());
+    }
+
+    #[test]
+    fn validate_alias_accepts_atext() {
+        let config = config();
+        assert!(validate_alias(&config, "a!#$%&'*+-/=?^_`{|}~z@example.com").is_ok());
+        assert!(validate_alias(&config, "first.last+tag@example.com").is_ok());
+        for address in [
+            "a b@example.com",
+            "a(b)@example.com",
+            "a,b@example.com",
+            "\"ab\"@example.com",
+            "a\\b@example.com",
+            "caf\u{e9}@example.com",
+        ] {
+            assert!(validate_alias(&config, address).is_err(), "{}", address);
         }
-        _ => {
-            // 默认使用字母数字组合
-            iter::repeat(())
-                .map(|()| rng.sample(Alphanumeric))
-                .map(char::from)
-                .take(config.alias.random_length)
-                .collect()
+    }
+
+    #[test]
+    fn validate_alias_checks_dots() {
+        let config = config();
+        for address in [".a@example.com", "a.@example.com", "a..b@example.com"] {
+            assert!(validate_alias(&config, address).is_err(), "{}", address);
+        }
+    }
+
+    #[test]
+    fn validate_alias_requires_configured_domain() {
+        let config = config_with(r#"subdomains = ["team"]"#, ALIAS);
+        assert!(validate_alias(&config, "a@team.example.com").is_ok());
+        assert!(validate_alias(&config, "a@other.example.com").is_err());
+        assert!(validate_alias(&config, "a@example.net").is_err());
+    }
+
+    #[test]
+    fn validate_alias_errors_are_localized() {
+        let config = config();
+        let long = format!("{}@example.com", "a".repeat(65));
+
+        with_locale(SupportedLocale::EnUS, || {
+            assert_eq!(
+                validate_alias(&config, &long).unwrap_err().to_string(),
+                format!(
+                    "The local part of {} is 65 bytes long, exceeding the 64-byte limit",
+                    long
+                )
+            );
+            assert_eq!(
+                validate_alias(&config, "a b@example.com")
+                    .unwrap_err()
+                    .to_string(),
+                "The local part of a b@example.com contains an illegal character: ' '"
+            );
+            assert_eq!(
+                validate_alias(&config, "a@example.net")
+                    .unwrap_err()
+                    .to_string(),
+                "Domain example.net is not a configured domain (expected example.com)"
+            );
+        });
+
+        with_locale(SupportedLocale::ZhCN, || {
+            assert_eq!(
+                validate_alias(&config, "a..b@example.com")
+                    .unwrap_err()
+                    .to_string(),
+                "a..b@example.com 的本地部分不能以点号开头或结尾，也不能包含连续的点号"
+            );
+            assert_eq!(
+                validate_alias(&config, "@example.com")
+                    .unwrap_err()
+                    .to_string(),
+                "@example.com 的本地部分为空"
+            );
+        });
+    }
+
+    #[test]
+    fn validate_options_checks_subdomain_and_prefix() {
+        let config = config_with(r#"subdomains = ["team"]"#, ALIAS);
+        let options = |prefix: Option<&str>, subdomain: Option<&str>| AliasOptions {
+            prefix: prefix.map(String::from),
+            subdomain: subdomain.map(String::from),
+            ..AliasOptions::default()
+        };
+
+        assert!(validate_options(&config, &options(None, None)).is_ok());
+        assert!(validate_options(&config, &options(Some("shop"), Some("team"))).is_ok());
+        assert!(validate_options(&config, &options(None, Some("-team"))).is_err());
+        assert!(validate_options(&config, &options(None, Some("te_am"))).is_err());
+        assert!(validate_options(&config, &options(Some("a..b"), None)).is_err());
+        // 未配置的子域名没有邮件路由
+        assert!(validate_options(&config, &options(Some("shop"), Some("other"))).is_err());
+    }
+
+    #[test]
+    fn validate_options_defers_subaddress_route_check() {
+        let config = config();
+        let options = AliasOptions {
+            subaddress: true,
+            base: Some("me".to_string()),
+            label: Some("GitHub".to_string()),
+            ..AliasOptions::default()
+        };
+
+        // 基础别名的路由在获取路由列表后才检查
+        assert!(validate_options(&config, &options).is_ok());
+        assert!(generate_alias(&config, &options, &[]).is_err());
+        assert_eq!(
+            generate_alias(&config, &options, &["me@example.com".to_string()]).unwrap(),
+            "me+github@example.com"
+        );
+
+        let invalid = AliasOptions {
+            base: Some("a..b".to_string()),
+            ..options
+        };
+        assert!(validate_options(&config, &invalid).is_err());
+    }
+
+    /// 使用指定字符集的配置
+    fn charset_config(charset: &str, extra: &str) -> Config {
+        config_with(
+            "",
+            &format!(
+                "prefix_mode = \"template\"\ncustom_prefixes = []\nrandom_length = 8\nrandom_charset = \"{}\"\n{}",
+                charset, extra
+            ),
+        )
+    }
+
+    fn render(config: &Config, template: &str, label: Option<&str>) -> Result<String> {
+        let options = AliasOptions {
+            label: label.map(String::from),
+            ..AliasOptions::default()
+        };
+        render_template(config, template, &options)
+    }
+
+    #[test]
+    fn render_template_keeps_literal_text() {
+        assert_eq!(
+            render(&config(), "shop.alias-1", None).unwrap(),
+            "shop.alias-1"
+        );
+    }
+
+    #[test]
+    fn render_template_slugifies_label() {
+        let config = config();
+        assert_eq!(
+            render(&config, "{label}.x", Some("My Shop (EU)")).unwrap(),
+            "my-shop-eu.x"
+        );
+        assert!(render(&config, "{label}", None).is_err());
+        // 只有符号的标签转换后为空
+        assert!(render(&config, "{label}", Some("!!!")).is_err());
+    }
+
+    #[test]
+    fn render_template_random_length() {
+        let config = config();
+        let rendered = render(&config, "a.{rand:4}", None).unwrap();
+        assert_eq!(rendered.len(), 6);
+        assert!(rendered[2..].chars().all(|c| c.is_ascii_lowercase()));
+        assert_eq!(render(&config, "{rand}", None).unwrap().len(), 8);
+        assert!(render(&config, "{rand:x}", None).is_err());
+        assert!(render(&config, "{rand:-1}", None).is_err());
+    }
+
+    #[test]
+    fn render_template_date() {
+        let rendered = render(&config(), "{date}", None).unwrap();
+        assert_eq!(rendered.len(), 8);
+        assert!(rendered.chars().all(|c| c.is_ascii_digit()));
+
+        // 渲染前后各取一次，避免恰好跨月时失败
+        let before = format!("x.{}", Local::now().format("%y%m"));
+        let rendered = render(&config(), "x.{date:%y%m}", None).unwrap();
+        let after = format!("x.{}", Local::now().format("%y%m"));
+        assert!(rendered == before || rendered == after, "{}", rendered);
+        assert!(render(&config(), "{date:%Q}", None).is_err());
+    }
+
+    #[test]
+    fn render_template_word_and_user() {
+        let rendered = render(&config(), "{word}", None).unwrap();
+        assert!(WORDLIST.contains(&rendered.as_str()));
+
+        if let Some(user) = current_user() {
+            assert_eq!(render(&config(), "{user}", None).unwrap(), user);
+        }
+    }
+
+    #[test]
+    fn render_template_rejects_unknown_placeholder() {
+        let error = render(&config(), "{label}.{slug}", Some("shop"))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("{slug}"), "{}", error);
+        assert!(render(&config(), "{Label}", Some("shop")).is_ok_and(|s| s == "{Label}"));
+    }
+
+    #[test]
+    fn render_template_rand_length_requires_character_charset() {
+        let config = charset_config("words", "word_count = 2\nword_digits = 0");
+        assert!(render(&config, "{rand:4}", None).is_err());
+
+        let rendered = render(&config, "{rand}", None).unwrap();
+        let words: Vec<&str> = rendered.split('-').collect();
+        assert_eq!(words.len(), 2);
+        assert!(words.iter().all(|word| WORDLIST.contains(word)));
+
+        // 可发音字符集按字符数生成
+        let config = charset_config("pronounceable", "");
+        assert_eq!(render(&config, "{rand:5}", None).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn word_prefix_uses_count_separator_and_digits() {
+        let config = charset_config(
+            "words",
+            "word_count = 3\nword_separator = \".\"\nword_digits = 4",
+        );
+        let prefix = generate_word_prefix(&config, &mut StdRng::seed_from_u64(7));
+        let parts: Vec<&str> = prefix.split('.').collect();
+
+        assert_eq!(parts.len(), 4, "{}", prefix);
+        assert!(parts[..3].iter().all(|word| WORDLIST.contains(word)));
+        assert_eq!(parts[3].len(), 4);
+        assert!(parts[3].chars().all(|c| c.is_ascii_digit()));
+        // 相同的随机源得到相同的结果
+        assert_eq!(
+            prefix,
+            generate_word_prefix(&config, &mut StdRng::seed_from_u64(7))
+        );
+    }
+
+    #[test]
+    fn word_prefix_without_digits() {
+        let config = charset_config("words", "word_count = 0\nword_digits = 0");
+        let prefix = generate_word_prefix(&config, &mut StdRng::seed_from_u64(1));
+        // 单词数量至少为1
+        assert!(WORDLIST.contains(&prefix.as_str()), "{}", prefix);
+    }
+
+    #[test]
+    fn pronounceable_prefix_alternates_consonants_and_vowels() {
+        let config = charset_config("pronounceable", "exclude_ambiguous = true");
+        let (consonants, vowels) = pronounceable_pools(&config);
+        assert!(!consonants.contains(&'l'));
+        assert!(!vowels.contains(&'o'));
+
+        let prefix = generate_pronounceable_prefix(&config, 9, &mut StdRng::seed_from_u64(3));
+        assert_eq!(prefix.len(), 9);
+        for (i, c) in prefix.chars().enumerate() {
+            let pool = if i % 2 == 0 { &consonants } else { &vowels };
+            assert!(pool.contains(&c), "{}", prefix);
         }
     }
+
+    #[test]
+    fn random_prefix_uses_charset_pool() {
+        let numeric = charset_config("numeric", "");
+        assert!(
+            generate_random_prefix(&numeric, 12)
+                .chars()
+                .all(|c| c.is_ascii_digit())
+        );
+
+        let unambiguous = charset_config("alphanumeric", "exclude_ambiguous = true");
+        let prefix = generate_random_prefix(&unambiguous, 200);
+        assert_eq!(prefix.len(), 200);
+        assert!(!prefix.contains(AMBIGUOUS_CHARS));
+    }
+
+    #[test]
+    fn entropy_of_character_charsets() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+        assert!(close(
+            random_entropy_bits(&charset_config("alphabetic", ""), 8),
+            8.0 * 26f64.log2()
+        ));
+        assert!(close(
+            random_entropy_bits(&charset_config("numeric", ""), 6),
+            6.0 * 10f64.log2()
+        ));
+        // 去除 0、o、1、l 后剩下32个字符，每个字符5比特
+        assert!(close(
+            random_entropy_bits(
+                &charset_config("alphanumeric", "exclude_ambiguous = true"),
+                8
+            ),
+            40.0
+        ));
+    }
+
+    #[test]
+    fn entropy_of_words_and_pronounceable() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+        let words = charset_config("words", "word_count = 3\nword_digits = 2");
+        assert!(close(
+            random_entropy_bits(&words, 100),
+            3.0 * (WORDLIST.len() as f64).log2() + 2.0 * 10f64.log2()
+        ));
+
+        // 辅音16个、元音5个
+        let pronounceable = charset_config("pronounceable", "");
+        assert!(close(
+            random_entropy_bits(&pronounceable, 5),
+            3.0 * 16f64.log2() + 2.0 * 5f64.log2()
+        ));
+    }
+
+    fn custom_config(prefixes: &[&str]) -> Config {
+        let prefixes: Vec<String> = prefixes.iter().map(|p| format!("\"{}\"", p)).collect();
+        config_with(
+            "",
+            &format!(
+                "prefix_mode = \"custom\"\ncustom_prefixes = [{}]\nrandom_length = 8\nrandom_charset = \"alphabetic\"",
+                prefixes.join(", ")
+            ),
+        )
+    }
+
+    fn taken(aliases: &[&str]) -> Vec<String> {
+        aliases.iter().map(|alias| alias.to_string()).collect()
+    }
+
+    #[test]
+    fn custom_pool_skips_existing_aliases() {
+        let config = custom_config(&["alpha", "beta", "gamma"]);
+        let existing = taken(&["alpha@example.com", "GAMMA@example.com"]);
+
+        for _ in 0..20 {
+            assert_eq!(
+                generate_alias(&config, &AliasOptions::default(), &existing).unwrap(),
+                "beta@example.com"
+            );
+        }
+    }
+
+    #[test]
+    fn custom_pool_exhaustion_is_an_error() {
+        let config = custom_config(&["alpha", "beta"]);
+        let existing = taken(&["alpha@example.com", "beta@example.com"]);
+
+        let error = generate_alias(&config, &AliasOptions::default(), &existing)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("custom_prefixes"), "{}", error);
+        assert!(generate_alias(&custom_config(&[]), &AliasOptions::default(), &[]).is_err());
+    }
+
+    #[test]
+    fn random_retries_give_up_when_every_prefix_is_taken() {
+        // 1位数字只有10种可能，全部被占用时在有限次尝试后报错
+        let config = config_with(
+            "",
+            "prefix_mode = \"random\"\ncustom_prefixes = []\nrandom_length = 1\nrandom_charset = \"numeric\"",
+        );
+        let all: Vec<String> = (0..10).map(|n| format!("{}@example.com", n)).collect();
+        let error = generate_alias(&config, &AliasOptions::default(), &all)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("random_length"), "{}", error);
+    }
+
+    #[test]
+    fn explicit_prefix_conflict_is_reported() {
+        let options = AliasOptions {
+            prefix: Some("shop".to_string()),
+            ..AliasOptions::default()
+        };
+        assert!(generate_alias(&config(), &options, &taken(&["shop@example.com"])).is_err());
+        assert_eq!(
+            generate_alias(&config(), &options, &taken(&["shop2@example.com"])).unwrap(),
+            "shop@example.com"
+        );
+    }
+
+    #[test]
+    fn template_without_random_part_reports_conflict() {
+        let config = config_with(
+            "",
+            "prefix_mode = \"template\"\ncustom_prefixes = []\nrandom_length = 8\nrandom_charset = \"alphabetic\"\ntemplate = \"{label}\"",
+        );
+        let options = AliasOptions {
+            label: Some("GitHub".to_string()),
+            ..AliasOptions::default()
+        };
+        let error = generate_alias(&config, &options, &taken(&["github@example.com"]))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("{rand}"), "{}", error);
+    }
+
+    fn deterministic_config(secret: &str, length: usize) -> Config {
+        config_with(
+            "",
+            &format!(
+                "prefix_mode = \"deterministic\"\ncustom_prefixes = []\nrandom_length = 8\nrandom_charset = \"alphabetic\"\nsecret = \"{}\"\ndeterministic_length = {}",
+                secret, length
+            ),
+        )
+    }
+
+    #[test]
+    fn deterministic_prefix_is_stable() {
+        let config = deterministic_config("team-secret", 10);
+        // HMAC-SHA256("team-secret", "alias:stripe") 的前10个 base32 字符
+        assert_eq!(
+            deterministic_prefix(&config, "stripe").unwrap(),
+            "3ztezzvmav"
+        );
+        // 标签按 slug 规范化
+        assert_eq!(
+            deterministic_prefix(&config, " Stripe ").unwrap(),
+            "3ztezzvmav"
+        );
+        assert!(deterministic_prefix(&config, "!!").is_err());
+    }
+
+    #[test]
+    fn deterministic_prefix_depends_on_secret_and_label() {
+        let config = deterministic_config("team-secret", 10);
+        let other_secret = deterministic_config("other-secret", 10);
+
+        let prefix = deterministic_prefix(&config, "stripe").unwrap();
+        assert_ne!(
+            prefix,
+            deterministic_prefix(&other_secret, "stripe").unwrap()
+        );
+        assert_ne!(prefix, deterministic_prefix(&config, "github").unwrap());
+
+        // 长度只截取同一个摘要
+        let long =
+            deterministic_prefix(&deterministic_config("team-secret", 20), "stripe").unwrap();
+        assert_eq!(long.len(), 20);
+        assert!(long.starts_with(&prefix));
+    }
+
+    #[test]
+    fn deterministic_mode_generates_alias_from_label() {
+        let config = deterministic_config("team-secret", 10);
+        let options = AliasOptions {
+            label: Some("stripe".to_string()),
+            ..AliasOptions::default()
+        };
+        assert_eq!(
+            generate_alias(&config, &options, &[]).unwrap(),
+            "3ztezzvmav@example.com"
+        );
+        // 确定性的别名已存在时直接报告冲突
+        assert!(generate_alias(&config, &options, &taken(&["3ztezzvmav@example.com"])).is_err());
+        assert!(generate_alias(&config, &AliasOptions::default(), &[]).is_err());
+    }
+
+    #[test]
+    fn verify_alias_round_trip() {
+        let config = deterministic_config("team-secret", 10);
+        let alias =
+            format_alias(&config, &deterministic_prefix(&config, "stripe").unwrap()).unwrap();
+
+        assert!(verify_alias(&config, &alias, "stripe").unwrap());
+        assert!(verify_alias(&config, &alias.to_uppercase(), "Stripe").unwrap());
+        assert!(verify_alias(&config, "3ztezzvmav", "stripe").unwrap());
+        assert!(!verify_alias(&config, &alias, "github").unwrap());
+    }
+
+    #[test]
+    fn verify_alias_rejects_tampered_alias() {
+        let config = deterministic_config("team-secret", 10);
+
+        assert!(!verify_alias(&config, "3ztezzvmaw@example.com", "stripe").unwrap());
+        assert!(!verify_alias(&config, "3ztezzvma@example.com", "stripe").unwrap());
+        assert!(!verify_alias(&config, "3ztezzvmav@example.net", "stripe").unwrap());
+        // 使用其他密钥派生的别名不匹配
+        let other = deterministic_config("other-secret", 10);
+        assert!(!verify_alias(&other, "3ztezzvmav@example.com", "stripe").unwrap());
+    }
+
+    #[test]
+    fn hmac_token_matches_reference() {
+        // 与 Python hmac + base64.b32encode 的结果一致
+        assert_eq!(
+            hmac_token(
+                "key",
+                "The quick brown fox jumps over the lazy dog",
+                MAX_HMAC_TOKEN_LENGTH
+            ),
+            "666ih5bqkoccjmjstdtku35ripxu2wnbjfdbowmxi6o3yli2htm"
+        );
+        assert_eq!(hmac_token("key", "message", 0), "");
+        assert_eq!(
+            hmac_token("key", "message", 100).len(),
+            MAX_HMAC_TOKEN_LENGTH
+        );
+    }
+
+    #[test]
+    fn registrable_domain_handles_public_suffixes() {
+        assert_eq!(registrable_domain("example.com"), "example.com");
+        assert_eq!(registrable_domain("mail.example.com"), "example.com");
+        assert_eq!(registrable_domain("a.b.example.co.uk"), "example.co.uk");
+        assert_eq!(registrable_domain("example.co.uk"), "example.co.uk");
+        assert_eq!(registrable_domain("shop.example.com.cn."), "example.com.cn");
+        assert_eq!(registrable_domain("Mail.Example.COM"), "example.com");
+        assert_eq!(registrable_domain("localhost"), "localhost");
+        assert_eq!(registrable_domain("10.0.0.1"), "10.0.0.1");
+    }
+
+    #[test]
+    fn random_prefix_is_lowercase_alphanumeric() {
+        let config = charset_config("alphanumeric", "");
+        let prefix = generate_random_prefix(&config, 5000);
+
+        assert!(
+            prefix
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        );
+        // 5000个字符中36个字符都应出现
+        let pool = charset_pool(&config).unwrap();
+        assert_eq!(pool.len(), 36);
+        assert!(pool.iter().all(|c| prefix.contains(*c)));
+    }
+
+    #[test]
+    fn unknown_charset_falls_back_to_alphanumeric() {
+        let config = charset_config("mixed", "");
+        assert_eq!(
+            charset_pool(&config),
+            charset_pool(&charset_config("alphanumeric", ""))
+        );
+    }
+
+    #[test]
+    fn random_mode_alias_uses_random_length() {
+        let config = config_with(
+            "",
+            "prefix_mode = \"random\"\ncustom_prefixes = []\nrandom_length = 12\nrandom_charset = \"alphanumeric\"\nexclude_ambiguous = true",
+        );
+        let alias = generate_alias(&config, &AliasOptions::default(), &[]).unwrap();
+        let (local, domain) = alias.split_once('@').unwrap();
+
+        assert_eq!(domain, "example.com");
+        assert_eq!(local.len(), 12);
+        assert!(!local.contains(AMBIGUOUS_CHARS));
+        assert_eq!(local, local.to_lowercase());
+    }
+
+    #[test]
+    fn random_suffix_is_lowercase() {
+        let suffix = random_suffix(64);
+        assert_eq!(suffix.len(), 64);
+        assert!(
+            suffix
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        );
+    }
+
+    #[test]
+    fn validate_subdomain_checks_each_label() {
+        assert_eq!(validate_subdomain(" Team ").unwrap(), "team");
+        assert_eq!(validate_subdomain("eu.team-1").unwrap(), "eu.team-1");
+        for subdomain in [
+            "",
+            "-team",
+            "team-",
+            "te_am",
+            "a..b",
+            "café",
+            &"a".repeat(64),
+        ] {
+            assert!(validate_subdomain(subdomain).is_err(), "{}", subdomain);
+        }
+        assert!(validate_subdomain(&"a".repeat(63)).is_ok());
+    }
+
+    #[test]
+    fn configured_domains_include_static_subdomains() {
+        let config = config_with(
+            r#"subdomains = ["Team", "eu.shop"]
+subdomain_template = "mail""#,
+            ALIAS,
+        );
+        assert!(is_configured_domain(&config, "Example.com"));
+        assert!(is_configured_domain(&config, "team.example.com"));
+        assert!(is_configured_domain(&config, "eu.shop.example.com"));
+        assert!(is_configured_domain(&config, "mail.example.com"));
+        assert!(!is_configured_domain(&config, "shop.example.com"));
+        assert!(!is_configured_domain(&config, "team.example.net"));
+        assert!(!is_configured_domain(&config, "notexample.com"));
+    }
+
+    #[test]
+    fn dynamic_template_accepts_any_valid_subdomain() {
+        let config = config_with(r#"subdomain_template = "{label}""#, ALIAS);
+        assert!(is_configured_domain(&config, "github.example.com"));
+        assert!(is_configured_domain(&config, "a.b.example.com"));
+        assert!(!is_configured_domain(&config, "bad_label.example.com"));
+    }
+
+    #[test]
+    fn alias_domain_prefers_option_over_template() {
+        let templated = config_with(r#"subdomain_template = "{label}""#, ALIAS);
+        let options = |label: Option<&str>, subdomain: Option<&str>| AliasOptions {
+            label: label.map(String::from),
+            subdomain: subdomain.map(String::from),
+            ..AliasOptions::default()
+        };
+
+        assert_eq!(
+            alias_domain(&templated, &options(Some("GitHub"), None)).unwrap(),
+            "github.example.com"
+        );
+        assert_eq!(
+            alias_domain(&templated, &options(Some("GitHub"), Some("Team"))).unwrap(),
+            "team.example.com"
+        );
+        assert!(alias_domain(&templated, &options(None, Some("te_am"))).is_err());
+        // 模板中的 {label} 缺失时报错
+        assert!(alias_domain(&templated, &options(None, None)).is_err());
+        assert_eq!(
+            alias_domain(&config(), &options(None, None)).unwrap(),
+            "example.com"
+        );
+    }
+
+    #[test]
+    fn site_prefix_strips_public_suffix() {
+        assert_eq!(
+            site_prefix("https://shop.example.com/signup").unwrap(),
+            "shop-example"
+        );
+        assert_eq!(site_prefix("www.example.co.uk").unwrap(), "example");
+        assert_eq!(
+            site_prefix("https://mail.example.com.").unwrap(),
+            "mail-example"
+        );
+        assert_eq!(
+            site_prefix("http://192.168.1.10:8080").unwrap(),
+            "192-168-1-10"
+        );
+        assert_eq!(site_prefix("Stripe").unwrap(), "stripe");
+        assert!(site_prefix("  ").is_err());
+    }
+
+    #[test]
+    fn hmac_token_uses_whole_digest() {
+        assert_eq!(
+            hmac_token("secret", "alias:github", MAX_HMAC_TOKEN_LENGTH).len(),
+            51
+        );
+    }
+
+    #[test]
+    fn recipient_matches_exact_address() {
+        assert!(recipient_matches(
+            "shop@example.com",
+            "shop@example.com",
+            "+"
+        ));
+        assert!(recipient_matches(
+            " Shop@Example.COM ",
+            "shop@example.com",
+            "+"
+        ));
+        assert!(!recipient_matches(
+            "shop@example.com",
+            "other@example.com",
+            "+"
+        ));
+        assert!(!recipient_matches(
+            "shop@example.com",
+            "shop@example.net",
+            "+"
+        ));
+    }
+
+    #[test]
+    fn recipient_matches_subaddresses_of_base_alias() {
+        assert!(recipient_matches(
+            "me@example.com",
+            "me+github@example.com",
+            "+"
+        ));
+        assert!(recipient_matches(
+            "me@example.com",
+            "ME+GitHub@example.com",
+            "+"
+        ));
+        // 子地址只在同一域名下匹配
+        assert!(!recipient_matches(
+            "me@example.com",
+            "me+github@example.net",
+            "+"
+        ));
+        // 前缀相同的其他别名不算子地址
+        assert!(!recipient_matches(
+            "me@example.com",
+            "meow@example.com",
+            "+"
+        ));
+        assert!(!recipient_matches(
+            "me@example.com",
+            "meow+x@example.com",
+            "+"
+        ));
+    }
+
+    #[test]
+    fn recipient_matches_subaddress_filter_exactly() {
+        assert!(recipient_matches(
+            "me+github@example.com",
+            "me+github@example.com",
+            "+"
+        ));
+        assert!(!recipient_matches(
+            "me+github@example.com",
+            "me+gitlab@example.com",
+            "+"
+        ));
+        assert!(!recipient_matches(
+            "me+github@example.com",
+            "me@example.com",
+            "+"
+        ));
+    }
+
+    #[test]
+    fn recipient_matches_custom_separator() {
+        assert!(recipient_matches(
+            "me@example.com",
+            "me-shop@example.com",
+            "-"
+        ));
+        assert!(!recipient_matches(
+            "me@example.com",
+            "me+shop@example.com",
+            "-"
+        ));
+        assert!(recipient_matches(
+            "me@example.com",
+            "me--shop@example.com",
+            "--"
+        ));
+        // 未设置分隔符时只做精确匹配
+        assert!(!recipient_matches(
+            "me@example.com",
+            "me+shop@example.com",
+            ""
+        ));
+        assert!(!recipient_matches("me", "me+shop@example.com", "+"));
+    }
+
+    #[test]
+    fn format_date_accepts_valid_format() {
+        let date = format_date("%Y%m%d").unwrap();
+        assert_eq!(date.len(), 8);
+        assert!(date.chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn format_date_rejects_invalid_format() {
+        assert!(format_date("%Q").is_err());
+        assert!(format_date("%Y%").is_err());
+    }
 }
@@ -0,0 +1,461 @@
+use crate::config::{ExtractConfig, ExtractRule};
+use crate::util::i18n::translate;
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashMap;
+
+/// 验证码提取器
+pub trait CodeExtractor {
+    /// 从某个发件人的邮件正文中找出所有候选验证码
+    fn candidates(&self, sender: &str, text: &str) -> Vec<Candidate>;
+}
+
+/// 内置规则: (名称, 正则, 优先级)，`{code}` 会替换为验证码片段的捕获组
+///
+/// 内置规则尽量宽松地捕获紧跟在提示词后的片段，再由调用方按验证码类型和长度过滤。
+const BUILTIN_RULES: &[(&str, &str, i32)] = &[
+    // "Enter the code 123456"格式
+    (
+        "enter-the-code",
+        r"(?i)enter the code(?:\s+below)?[\s:：]*\b{code}",
+        60,
+    ),
+    // "The verification code is 123456"格式
+    (
+        "verification-code",
+        r"(?i)verification code(?:\s+is)?[\s:：]*\b{code}",
+        60,
+    ),
+    // "Your code is 123456"格式
+    ("your-code", r"(?i)your code(?:\s+is)?[\s:：]*\b{code}", 60),
+    // "Code: 123456"格式，要求紧跟冒号或 is，避免匹配正文中任意位置的 code
+    ("code-colon", r"(?i)\bcode(?:\s+is|\s*[:：])\s*\b{code}", 50),
+    // "您的验证码是：123456"格式，与界面语言无关（中文与数字之间没有单词边界）
+    (
+        "zh-code",
+        r"(?:验证码|校验码|动态码|确认码|动态密码)(?:为|是)?[\s:：]*{code}",
+        60,
+    ),
+    // 单独成行的片段 - 通常邮件中单独一行的数字很可能是验证码
+    ("standalone-line", r"(?m)^[ \t]*{code}[ \t]*$", 40),
+];
+
+/// 验证码片段：字母数字，中间可以有连字符（如 AB-12-CD）
+const CODE_TOKEN: &str = r"([0-9A-Za-z](?:[0-9A-Za-z-]*[0-9A-Za-z])?)";
+
+/// 验证码提示词规则的优先级
+const MARKER_PRIORITY: i32 = 30;
+
+/// 编译后的提取规则
+struct CompiledRule {
+    name: String,
+    sender: Option<Regex>,
+    pattern: Regex,
+    group: usize,
+    priority: i32,
+    custom: bool,
+}
+
+impl CompiledRule {
+    fn from_config(index: usize, rule: &ExtractRule) -> Result<Self> {
+        let name = rule
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("rule-{}", index + 1));
+        let sender = match &rule.sender {
+            Some(sender) => Some(
+                Regex::new(&format!("(?i){}", sender))
+                    .with_context(|| format!("提取规则 {} 的发件人正则无效", name))?,
+            ),
+            None => None,
+        };
+        let pattern =
+            Regex::new(&rule.pattern).with_context(|| format!("提取规则 {} 的正则无效", name))?;
+        if rule.group > pattern.captures_len() - 1 {
+            anyhow::bail!("提取规则 {} 没有第 {} 个捕获组", name, rule.group);
+        }
+
+        Ok(Self {
+            name,
+            sender,
+            pattern,
+            group: rule.group,
+            priority: rule.priority,
+            custom: true,
+        })
+    }
+
+    fn builtin(name: &str, pattern: &str, priority: i32) -> Self {
+        Self {
+            name: name.to_string(),
+            sender: None,
+            pattern: Regex::new(&pattern.replace("{code}", CODE_TOKEN)).expect("内置提取规则无效"),
+            group: 1,
+            priority,
+            custom: false,
+        }
+    }
+
+    fn applies_to(&self, sender: &str) -> bool {
+        self.sender
+            .as_ref()
+            .is_none_or(|regex| regex.is_match(sender))
+    }
+
+    fn candidates(&self, text: &str) -> Vec<Candidate> {
+        self.pattern
+            .captures_iter(text)
+            .filter_map(|caps| caps.get(self.group))
+            .filter(|m| !m.as_str().trim().is_empty())
+            .map(|m| {
+                let raw = m.as_str();
+                Candidate {
+                    code: raw.trim().to_string(),
+                    // 记录去除前导空白后验证码本身的位置
+                    start: m.start() + (raw.len() - raw.trim_start().len()),
+                    rule: self.name.clone(),
+                    priority: self.priority,
+                    custom: self.custom,
+                }
+            })
+            .collect()
+    }
+}
+
+/// 基于规则的验证码提取器
+///
+/// 配置中的自定义规则与内置规则一起按优先级排列，所有规则的匹配都作为候选。
+pub struct RuleExtractor {
+    rules: Vec<CompiledRule>,
+}
+
+impl RuleExtractor {
+    /// 根据提取配置创建提取器
+    pub fn from_config(config: &ExtractConfig) -> Result<Self> {
+        let mut rules = config
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| CompiledRule::from_config(index, rule))
+            .collect::<Result<Vec<_>>>()?;
+
+        rules.extend(
+            BUILTIN_RULES
+                .iter()
+                .map(|(name, pattern, priority)| CompiledRule::builtin(name, pattern, *priority)),
+        );
+
+        // 验证码提示词（随界面语言变化）
+        let code_markers = [
+            translate("code_markers.verification_code"),
+            translate("code_markers.code"),
+            translate("code_markers.password"),
+            translate("code_markers.check_code"),
+            translate("code_markers.dynamic_code"),
+            translate("code_markers.token"),
+            translate("code_markers.verification_token"),
+            translate("code_markers.dynamic_password"),
+            translate("code_markers.verification"),
+            "verification".to_string(),
+        ];
+        for marker in &code_markers {
+            rules.push(CompiledRule::builtin(
+                &format!("marker:{}", marker),
+                &format!(r"{}[\s:：]*{{code}}", regex::escape(marker)),
+                MARKER_PRIORITY,
+            ));
+        }
+
+        // 稳定排序，同优先级时自定义规则在前
+        rules.sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+        Ok(Self { rules })
+    }
+}
+
+impl CodeExtractor for RuleExtractor {
+    fn candidates(&self, sender: &str, text: &str) -> Vec<Candidate> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.applies_to(sender))
+            .flat_map(|rule| rule.candidates(text))
+            .collect()
+    }
+}
+
+/// 候选验证码
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    /// 验证码
+    pub code: String,
+    /// 在正文中的字节位置
+    pub start: usize,
+    /// 产生该候选的规则
+    pub rule: String,
+    /// 规则优先级
+    pub priority: i32,
+    /// 是否来自配置中的自定义规则
+    pub custom: bool,
+}
+
+/// 评分后的验证码
+#[derive(Debug, Clone)]
+pub struct ScoredCode {
+    /// 验证码
+    pub code: String,
+    /// 评分，越高越可能是验证码
+    pub score: i32,
+    /// 置信度（0.0 - 1.0）
+    pub confidence: f32,
+    /// 产生该验证码的规则
+    pub rules: Vec<String>,
+    /// 评分依据
+    pub reasons: Vec<String>,
+}
+
+/// 置信度低于该值的验证码不会被自动复制
+pub const LOW_CONFIDENCE: f32 = 0.5;
+
+/// 评分达到该值时置信度为 1.0
+const FULL_CONFIDENCE_SCORE: i32 = 12;
+
+/// 候选前方出现这些提示词时更可能是验证码
+const CODE_HINTS: &[&str] = &[
+    "code",
+    "verification",
+    "verify",
+    "otp",
+    "pin",
+    "passcode",
+    "one-time",
+    "验证码",
+    "校验码",
+    "动态码",
+    "确认码",
+    "验证代码",
+    "动态密码",
+];
+
+/// 候选前方出现这些词时通常是订单号、电话等
+const NON_CODE_HINTS: &[&str] = &[
+    "order", "invoice", "#", "tel", "phone", "fax", "ref", "订单", "编号", "电话", "单号",
+];
+
+/// HTML 中加粗或放大的文字
+static EMPHASIS_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?is)<(?:b|strong|h[1-3])\b[^>]*>|style\s*=\s*"[^"]*(?:font-size\s*:\s*(?:[2-9]\d|\d{3})px|font-weight\s*:\s*(?:bold|[6-9]00))[^"]*"[^>]*>"#,
+    )
+    .unwrap()
+});
+
+/// 评分时使用的上下文
+pub struct ScoringContext<'a> {
+    /// 提取候选时使用的正文
+    pub text: &'a str,
+    /// 原始HTML正文（如有），用于判断样式
+    pub html: Option<&'a str>,
+    /// 用户指定的验证码长度
+    pub expected_length: Option<usize>,
+}
+
+/// 为同一正文中的候选验证码评分
+pub fn score_candidates(candidates: &[Candidate], context: &ScoringContext) -> Vec<ScoredCode> {
+    candidates
+        .iter()
+        .map(|candidate| {
+            let mut reasons = Vec::new();
+            let mut score = candidate.priority / 10;
+            reasons.push(format!("{} {:+}", candidate.rule, score));
+
+            let mut adjust = |points: i32, reason: &str| {
+                score += points;
+                reasons.push(format!("{} {:+}", reason, points));
+            };
+
+            let before = text_before(context.text, candidate.start, 40).to_lowercase();
+            if CODE_HINTS.iter().any(|hint| before.contains(hint)) {
+                adjust(3, "near-marker");
+            }
+            if NON_CODE_HINTS.iter().any(|hint| before.contains(hint)) {
+                adjust(-4, "order-or-phone");
+            }
+            if is_on_own_line(context.text, candidate.start, &candidate.code) {
+                adjust(2, "own-line");
+            }
+            if let Some(html) = context.html
+                && is_emphasized(html, &candidate.code)
+            {
+                adjust(3, "emphasized");
+            }
+            match context.expected_length {
+                Some(len) if candidate.code.chars().count() == len => adjust(2, "length-match"),
+                Some(_) => adjust(-3, "length-mismatch"),
+                None if candidate.code.chars().count() == 6 => adjust(1, "typical-length"),
+                None => {}
+            }
+            if looks_like_date(context.text, candidate.start, &candidate.code) {
+                adjust(-5, "date-like");
+            }
+            if !candidate.code.chars().any(|c| c.is_ascii_digit()) {
+                adjust(-4, "no-digits");
+            }
+
+            ScoredCode {
+                code: candidate.code.clone(),
+                score,
+                confidence: 0.0,
+                rules: vec![candidate.rule.clone()],
+                reasons,
+            }
+        })
+        .collect()
+}
+
+/// 合并多个正文中的评分结果，按评分从高到低排序并计算置信度
+pub fn rank(scored: Vec<ScoredCode>) -> Vec<ScoredCode> {
+    let mut merged: Vec<ScoredCode> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut hits: HashMap<String, usize> = HashMap::new();
+
+    for code in scored {
+        *hits.entry(code.code.clone()).or_default() += 1;
+        match index.get(&code.code) {
+            Some(&i) => {
+                let existing = &mut merged[i];
+                for rule in code.rules {
+                    if !existing.rules.contains(&rule) {
+                        existing.rules.push(rule);
+                    }
+                }
+                if code.score > existing.score {
+                    existing.score = code.score;
+                    existing.reasons = code.reasons;
+                }
+            }
+            None => {
+                index.insert(code.code.clone(), merged.len());
+                merged.push(code);
+            }
+        }
+    }
+
+    // 被多条规则或多个位置同时找到的验证码更可信
+    for code in &mut merged {
+        if hits[&code.code] > 1 {
+            code.score += 1;
+            code.reasons.push("repeated +1".to_string());
+        }
+        code.confidence = (code.score as f32 / FULL_CONFIDENCE_SCORE as f32).clamp(0.0, 1.0);
+    }
+
+    // 评分不为正的候选基本可以确定不是验证码
+    merged.retain(|code| code.score > 0);
+    merged.sort_by_key(|code| std::cmp::Reverse(code.score));
+
+    // 前两名分数接近时结果不明确，降低置信度
+    if merged.len() > 1 && merged[0].score - merged[1].score < 2 {
+        merged[0].confidence = (merged[0].confidence - 0.2).max(0.0);
+        merged[0].reasons.push("ambiguous".to_string());
+    }
+
+    merged
+}
+
+/// 获取某个位置之前最多 `max_chars` 个字符
+fn text_before(text: &str, start: usize, max_chars: usize) -> &str {
+    let prefix = text.get(..start).unwrap_or_default();
+    let begin = prefix
+        .char_indices()
+        .rev()
+        .nth(max_chars.saturating_sub(1))
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    &prefix[begin..]
+}
+
+/// 候选是否单独占据一行
+fn is_on_own_line(text: &str, start: usize, code: &str) -> bool {
+    let (Some(before), Some(after)) = (text.get(..start), text.get(start..)) else {
+        return false;
+    };
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = after.find('\n').map(|i| start + i).unwrap_or(text.len());
+    text[line_start..line_end].trim().trim_matches(['*', '_']) == code
+}
+
+/// 候选在HTML中是否被加粗或放大显示
+fn is_emphasized(html: &str, code: &str) -> bool {
+    html.match_indices(code).any(|(pos, _)| {
+        // 找到候选所在元素的开始标签
+        let before = &html[..pos];
+        let Some(tag_start) = before.rfind('<') else {
+            return false;
+        };
+        // 候选与开始标签之间只能有空白或其他标签
+        let tag = &before[tag_start..];
+        let between = tag.find('>').map(|i| &tag[i + 1..]).unwrap_or_default();
+        between.trim().is_empty() && EMPHASIS_REGEX.is_match(tag)
+    })
+}
+
+/// 候选是否像年份或日期时间的一部分
+fn looks_like_date(text: &str, start: usize, code: &str) -> bool {
+    if code.len() == 4
+        && let Ok(year) = code.parse::<u32>()
+        && (1900..=2099).contains(&year)
+    {
+        return true;
+    }
+
+    // 与 / - . : 及数字相连，例如 2024-05-01、12:30
+    let prev = text
+        .get(..start)
+        .unwrap_or_default()
+        .chars()
+        .rev()
+        .take(2)
+        .collect::<Vec<_>>();
+    let next = text
+        .get(start + code.len()..)
+        .unwrap_or_default()
+        .chars()
+        .take(2)
+        .collect::<Vec<_>>();
+    let joined = |chars: &[char]| matches!(chars, [sep, digit, ..] if "/-.:".contains(*sep) && digit.is_ascii_digit());
+    joined(&prev) || joined(&next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_rule_with_leading_whitespace_before_non_ascii_code() {
+        let rule = ExtractRule {
+            name: None,
+            sender: None,
+            pattern: r"口令:(\s*\S+)".to_string(),
+            group: 1,
+            priority: 100,
+        };
+        let rule = CompiledRule::from_config(0, &rule).unwrap();
+        let text = "口令:  验证一二三 请勿泄露";
+
+        let candidates = rule.candidates(text);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].code, "验证一二三");
+        assert_eq!(
+            &text[candidates[0].start..][..candidates[0].code.len()],
+            "验证一二三"
+        );
+
+        let context = ScoringContext {
+            text,
+            html: None,
+            expected_length: None,
+        };
+        let scored = score_candidates(&candidates, &context);
+        assert_eq!(scored[0].code, "验证一二三");
+    }
+}
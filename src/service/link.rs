@@ -0,0 +1,359 @@
+use crate::service::email::registrable_domain;
+use mail_parser::{HeaderValue, Message};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// HTML 中的链接
+static ANCHOR_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?is)<a\b[^>]*?\bhref\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))[^>]*>(.*?)</a>"#)
+        .unwrap()
+});
+
+/// 纯文本中的网址
+static URL_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#"https?://[^\s<>"'()\[\]]+"#).unwrap());
+
+/// HTML 标签
+static TAG_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+
+/// 验证链接常见的关键词
+const VERIFY_KEYWORDS: &[&str] = &[
+    "verify",
+    "verification",
+    "confirm",
+    "activate",
+    "activation",
+    "validate",
+    "magic",
+    "sign in",
+    "sign-in",
+    "signin",
+    "log in",
+    "login",
+    "验证",
+    "确认",
+    "激活",
+    "登录",
+];
+
+/// 通常不是验证链接的关键词
+const IGNORE_KEYWORDS: &[&str] = &[
+    "unsubscribe",
+    "preferences",
+    "privacy",
+    "terms",
+    "policy",
+    "help",
+    "support",
+    "退订",
+    "隐私",
+    "条款",
+    "帮助",
+];
+
+/// 图片等静态资源的扩展名
+const ASSET_EXTENSIONS: &[&str] = &[".png", ".jpg", ".jpeg", ".gif", ".svg", ".css", ".js"];
+
+/// 候选链接
+#[derive(Debug, Clone)]
+pub struct LinkCandidate {
+    /// 链接地址
+    pub url: String,
+    /// 链接文字或所在行的文本
+    pub text: String,
+    /// 排序分数，越高越可能是验证链接
+    pub score: i32,
+}
+
+/// 提取邮件中的链接，并按验证链接的可能性从高到低排序
+///
+/// 只返回分数为正的候选链接。
+pub fn rank_links(message: &Message) -> Vec<LinkCandidate> {
+    let sender_domain = sender_domain(message);
+    let mut candidates: Vec<LinkCandidate> = Vec::new();
+
+    // 只有 HTML 正文时 text_bodies 也会返回 HTML 部分，需要按类型区分
+    let found = message
+        .html_bodies()
+        .filter(|part| part.is_text_html())
+        .filter_map(|part| std::str::from_utf8(part.contents()).ok())
+        .flat_map(find_anchors)
+        .chain(
+            message
+                .text_bodies()
+                .filter(|part| !part.is_text_html())
+                .filter_map(|part| std::str::from_utf8(part.contents()).ok())
+                .flat_map(find_urls),
+        );
+
+    for (url, text) in found {
+        let score = score_link(&url, &text, sender_domain.as_deref());
+        match candidates.iter_mut().find(|c| c.url == url) {
+            Some(existing) if existing.score < score => {
+                existing.score = score;
+                existing.text = text;
+            }
+            Some(_) => {}
+            None => candidates.push(LinkCandidate { url, text, score }),
+        }
+    }
+
+    candidates.retain(|c| c.score > 0);
+    // 稳定排序，同分时保留邮件中的先后顺序
+    candidates.sort_by_key(|c| std::cmp::Reverse(c.score));
+    candidates
+}
+
+/// 解析 HTML 中的 `<a href>` 链接及其文字
+fn find_anchors(html: &str) -> Vec<(String, String)> {
+    ANCHOR_REGEX
+        .captures_iter(html)
+        .filter_map(|caps| {
+            let href = caps.get(1).or(caps.get(2)).or(caps.get(3))?.as_str();
+            let text = TAG_REGEX.replace_all(&caps[4], " ");
+            Some((
+                decode_entities(href.trim()),
+                normalize_text(&decode_entities(&text)),
+            ))
+        })
+        .filter(|(url, _)| is_web_url(url))
+        .collect()
+}
+
+/// 查找纯文本中的网址，以所在行作为链接文字
+fn find_urls(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .flat_map(|line| {
+            URL_REGEX.find_iter(line).map(move |m| {
+                let url = m.as_str().trim_end_matches(['.', ',', ';', ':', '!', '?']);
+                (url.to_string(), normalize_text(line))
+            })
+        })
+        .collect()
+}
+
+/// 根据关键词和域名为链接打分
+fn score_link(url: &str, text: &str, sender_domain: Option<&str>) -> i32 {
+    let url_lower = url.to_lowercase();
+    let text_lower = text.to_lowercase();
+    let mut score = 0;
+
+    for keyword in VERIFY_KEYWORDS {
+        if text_lower.contains(keyword) {
+            score += 3;
+        }
+        if url_lower.contains(keyword) {
+            score += 2;
+        }
+    }
+
+    if IGNORE_KEYWORDS
+        .iter()
+        .any(|keyword| text_lower.contains(keyword) || url_lower.contains(keyword))
+    {
+        score -= 5;
+    }
+
+    let path = url_lower.split(['?', '#']).next().unwrap_or_default();
+    if ASSET_EXTENSIONS.iter().any(|ext| path.ends_with(ext)) {
+        score -= 5;
+    }
+
+    // 与发件人同域的链接更可信
+    if let Some(sender_domain) = sender_domain
+        && let Ok(parsed) = reqwest::Url::parse(url)
+        && let Some(host) = parsed.host_str()
+        && registrable_domain(host) == sender_domain
+    {
+        score += 2;
+    }
+
+    score
+}
+
+/// 获取发件人地址的主域名
+fn sender_domain(message: &Message) -> Option<String> {
+    let address = match message.from() {
+        HeaderValue::Address(addr) => addr.address.as_deref(),
+        HeaderValue::AddressList(list) => list.first().and_then(|addr| addr.address.as_deref()),
+        _ => None,
+    }?;
+
+    let (_, domain) = address.rsplit_once('@')?;
+    Some(registrable_domain(domain))
+}
+
+fn is_web_url(url: &str) -> bool {
+    let lower = url.to_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// 解码链接和文字中常见的 HTML 实体
+fn decode_entities(text: &str) -> String {
+    text.replace("&amp;", "&")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+}
+
+/// 合并多余的空白字符
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(raw: &str) -> Message<'_> {
+        Message::parse(raw.as_bytes()).unwrap()
+    }
+
+    fn html_mail(from: &str, html: &str) -> String {
+        format!(
+            "From: {}\r\nSubject: Welcome\r\nContent-Type: text/html; charset=utf-8\r\n\r\n{}\r\n",
+            from, html
+        )
+    }
+
+    fn urls(candidates: &[LinkCandidate]) -> Vec<&str> {
+        candidates.iter().map(|c| c.url.as_str()).collect()
+    }
+
+    #[test]
+    fn verify_keywords_rank_first() {
+        let raw = html_mail(
+            "noreply@example.com",
+            r#"<a href="https://example.com/blog">Read our blog</a>
+<a href="https://example.com/welcome?t=1">Activate account</a>
+<a href="https://example.com/verify?t=2">Verify email</a>"#,
+        );
+        let ranked = rank_links(&parse(&raw));
+
+        // 文字和地址都包含关键词的链接优先，没有关键词的链接只有同域加分
+        assert_eq!(
+            urls(&ranked),
+            [
+                "https://example.com/verify?t=2",
+                "https://example.com/welcome?t=1",
+                "https://example.com/blog",
+            ]
+        );
+    }
+
+    #[test]
+    fn sender_domain_breaks_ties() {
+        let raw = html_mail(
+            "Service <noreply@mail.service.co.uk>",
+            r#"<a href="https://tracker.example.net/confirm">Confirm</a>
+<a href="https://links.service.co.uk/confirm">Confirm</a>"#,
+        );
+        let ranked = rank_links(&parse(&raw));
+
+        assert_eq!(ranked[0].url, "https://links.service.co.uk/confirm");
+        assert_eq!(ranked[0].score, ranked[1].score + 2);
+    }
+
+    #[test]
+    fn unsubscribe_and_assets_are_penalized() {
+        assert!(score_link("https://example.com/unsubscribe", "Unsubscribe", None) < 0);
+        assert!(
+            score_link(
+                "https://example.com/verify",
+                "Verify · Privacy policy",
+                None
+            ) < 6
+        );
+        assert!(score_link("https://example.com/verify/logo.png?v=1", "", None) < 0);
+
+        let raw = html_mail(
+            "noreply@example.com",
+            r#"<a href="https://example.com/verify"><img src="https://example.com/logo.png"></a>
+<a href="https://example.com/unsubscribe?verify=1">Unsubscribe</a>
+<a href="https://cdn.example.com/banner.jpg">View</a>"#,
+        );
+        assert_eq!(
+            urls(&rank_links(&parse(&raw))),
+            ["https://example.com/verify"]
+        );
+    }
+
+    #[test]
+    fn non_positive_links_are_dropped() {
+        let raw = html_mail(
+            "noreply@example.com",
+            r#"<a href="https://other.example.net/">Home</a><a href="mailto:help@example.com">Mail us</a>"#,
+        );
+        assert!(rank_links(&parse(&raw)).is_empty());
+    }
+
+    #[test]
+    fn anchors_support_all_href_quotes() {
+        let anchors = find_anchors(
+            r#"<a class="btn" href="https://a.example/1">One</a>
+<A HREF='https://a.example/2'><b>Two</b>
+  words</A>
+<a href=https://a.example/3 target=_blank>Three</a>
+<a href="/relative">Relative</a>"#,
+        );
+        assert_eq!(
+            anchors,
+            [
+                ("https://a.example/1".to_string(), "One".to_string()),
+                ("https://a.example/2".to_string(), "Two words".to_string()),
+                ("https://a.example/3".to_string(), "Three".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn href_entities_are_decoded() {
+        let anchors = find_anchors(
+            r#"<a href="https://example.com/verify?a=1&amp;b=2&amp;c=&quot;x&quot;">Verify&nbsp;&amp;&nbsp;continue</a>"#,
+        );
+        assert_eq!(
+            anchors,
+            [(
+                "https://example.com/verify?a=1&b=2&c=\"x\"".to_string(),
+                "Verify & continue".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn bare_urls_use_their_line_as_text() {
+        let urls = find_urls(
+            "Hello,\nVerify your email: https://example.com/verify?t=abc.\n(see https://example.com/help)\n",
+        );
+        assert_eq!(
+            urls,
+            [
+                (
+                    "https://example.com/verify?t=abc".to_string(),
+                    "Verify your email: https://example.com/verify?t=abc.".to_string()
+                ),
+                (
+                    "https://example.com/help".to_string(),
+                    "(see https://example.com/help)".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn plain_text_and_html_parts_are_merged() {
+        let raw = "From: noreply@example.com\r\nSubject: Welcome\r\nMIME-Version: 1.0\r\nContent-Type: multipart/alternative; boundary=\"b\"\r\n\r\n--b\r\nContent-Type: text/plain; charset=utf-8\r\n\r\nOpen https://example.com/verify?t=1 to continue\r\n--b\r\nContent-Type: text/html; charset=utf-8\r\n\r\n<a href=\"https://example.com/verify?t=1\">Verify email</a>\r\n--b--\r\n";
+        let ranked = rank_links(&parse(raw));
+
+        // 同一链接只保留一次，使用得分更高的 HTML 链接文字
+        assert_eq!(urls(&ranked), ["https://example.com/verify?t=1"]);
+        assert_eq!(ranked[0].text, "Verify email");
+    }
+
+    #[test]
+    fn sender_domain_uses_registrable_domain() {
+        let raw = html_mail("Shop <NoReply@Mail.Shop.Example.com>", "");
+        assert_eq!(sender_domain(&parse(&raw)).as_deref(), Some("example.com"));
+    }
+}
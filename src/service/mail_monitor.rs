@@ -1,10 +1,42 @@
-use crate::config::Config;
+use crate::config::{Config, ExtractConfig};
+use crate::service::email::recipient_matches;
+use crate::service::extractor::{
+    Candidate, CodeExtractor, LOW_CONFIDENCE, RuleExtractor, ScoredCode, ScoringContext, rank,
+    score_candidates,
+};
+use crate::service::link;
+use crate::service::oauth::{self, XOAuth2};
 use crate::util::i18n::{translate, translate_args};
+use crate::util::imap_utf7::{decode_mailbox, encode_mailbox};
 use anyhow::{Context, Result, anyhow};
+use async_imap::Session;
+use async_imap::types::{Mailbox, NameAttribute};
 use chrono::{DateTime, Utc};
-use imap::Session;
-use mail_parser::Message;
+use futures::TryStreamExt;
+use mail_parser::{Addr, HeaderValue, Message};
 use regex::Regex;
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio::time::{Instant, sleep};
+
+/// IMAP连接的数据流（TLS或明文）
+trait ImapStream: AsyncRead + AsyncWrite + Unpin + Send + std::fmt::Debug {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + std::fmt::Debug> ImapStream for T {}
+
+/// 已登录的IMAP会话
+type ImapSession = Session<Box<dyn ImapStream>>;
+
+/// 默认超时时间（秒）
+pub const DEFAULT_TIMEOUT: u64 = 300;
+
+/// 默认轮询间隔（秒），命令行各监听命令共用
+pub const DEFAULT_POLL_INTERVAL: u64 = 3;
+
+/// IDLE 的最长持续时间，超过后重新发起以免被服务器断开（RFC 2177 建议不超过29分钟）
+const IDLE_REFRESH: Duration = Duration::from_secs(5 * 60);
 
 /// 验证码类型
 #[derive(Debug, Clone, Copy)]
@@ -17,71 +49,257 @@ pub enum CodeType {
     Any,
 }
 
+/// 未指定长度时内置规则接受的验证码长度
+const DEFAULT_CODE_LENGTH: std::ops::RangeInclusive<usize> = 4..=8;
+
 impl CodeType {
-    /// 获取对应的正则表达式模式
+    /// 获取对应的正则表达式模式，验证码位于第1个捕获组
     fn pattern(&self, length: Option<usize>) -> String {
+        let class = match self {
+            Self::Numeric => r"\d",
+            Self::Alphanumeric => "[A-Za-z0-9]",
+            Self::Any => r"\S",
+        };
+        let repeat = match length {
+            Some(len) => format!("{{{}}}", len),
+            None => format!(
+                "{{{},{}}}",
+                DEFAULT_CODE_LENGTH.start(),
+                DEFAULT_CODE_LENGTH.end()
+            ),
+        };
+        format!(r"\b({}{})\b", class, repeat)
+    }
+
+    /// 验证码的字符是否符合类型
+    fn matches(&self, code: &str) -> bool {
         match self {
-            Self::Numeric => {
-                if let Some(len) = length {
-                    format!(r"\b\d{{{}}}\b", len)
-                } else {
-                    r"\b\d{4,8}\b".to_string()
-                }
-            }
-            Self::Alphanumeric => {
-                if let Some(len) = length {
-                    format!(r"\b[A-Za-z0-9]{{{}}}\b", len)
-                } else {
-                    r"\b[A-Za-z0-9]{4,8}\b".to_string()
-                }
-            }
-            Self::Any => {
-                if let Some(len) = length {
-                    format!(r"\b\S{{{}}}\b", len)
-                } else {
-                    r"\b\S{4,8}\b".to_string()
-                }
-            }
+            Self::Numeric => code.chars().all(|c| c.is_ascii_digit()),
+            Self::Alphanumeric => code.chars().all(|c| c.is_ascii_alphanumeric()),
+            Self::Any => !code.chars().any(char::is_whitespace),
         }
     }
 }
 
+/// 找到结果后如何处理来源邮件
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum MessageAction {
+    /// 保留在原文件夹
+    #[default]
+    Keep,
+    /// 移动到指定文件夹
+    MoveTo(String),
+    /// 删除
+    Delete,
+}
+
 /// 邮箱监听配置选项
 pub struct MonitorOptions {
     /// 验证码长度
     pub code_length: Option<usize>,
-    /// 验证码类型
-    pub code_type: CodeType,
+    /// 验证码类型，未指定时内置规则只接受纯数字
+    pub code_type: Option<CodeType>,
     /// 发件人过滤
     pub from_filter: Option<String>,
+    /// 收件人过滤（基础别名同时匹配其子地址）
+    pub to_filter: Option<String>,
+    /// 额外接收监听开始前这段时间内发送的邮件（默认只接收监听开始后送达的邮件）
+    pub since: Option<Duration>,
     /// 超时时间（秒）
     pub timeout: u64,
     /// 轮询间隔（秒）
     pub poll_interval: u64,
+    /// 是否将找到结果的邮件标记为已读（其他邮件始终保持原状态）
+    pub mark_seen: bool,
+    /// 找到结果后对来源邮件的处理
+    pub action: MessageAction,
+    /// 要监听的文件夹，为空时使用配置文件中的 smtp.folders
+    pub folders: Vec<String>,
 }
 
 impl Default for MonitorOptions {
     fn default() -> Self {
         Self {
             code_length: None,
-            code_type: CodeType::Numeric,
+            code_type: None,
             from_filter: None,
-            timeout: 300,      // 5分钟超时
-            poll_interval: 10, // 10秒轮询一次
+            to_filter: None,
+            since: None,
+            timeout: DEFAULT_TIMEOUT,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            mark_seen: true,
+            action: MessageAction::Keep,
+            folders: Vec::new(),
         }
     }
 }
 
 /// 邮件验证码结果
+#[allow(dead_code)]
 pub struct CodeResult {
     /// 提取到的验证码
     pub code: String,
+    /// 置信度（0.0 - 1.0）
+    pub confidence: f32,
+    /// 来源邮件标题
+    pub subject: String,
+    /// 发件人
+    pub from: String,
+}
+
+/// 邮件验证链接结果
+#[allow(dead_code)]
+pub struct LinkResult {
+    /// 验证链接
+    pub url: String,
+    /// 链接文字
+    pub text: String,
     /// 来源邮件标题
     pub subject: String,
     /// 发件人
     pub from: String,
 }
 
+/// 监听得到的结果
+trait WatchResult {
+    /// 结果是否可信，只有可信的结果才会按选项处理来源邮件
+    fn is_confident(&self) -> bool;
+}
+
+impl WatchResult for CodeResult {
+    fn is_confident(&self) -> bool {
+        // 置信度低时需要用户自行核对邮件，因此保留邮件原状
+        self.confidence >= LOW_CONFIDENCE
+    }
+}
+
+impl WatchResult for LinkResult {
+    fn is_confident(&self) -> bool {
+        true
+    }
+}
+
+/// 服务器上的文件夹
+pub struct FolderInfo {
+    /// 文件夹名称（用于配置和 --folder 参数）
+    pub name: String,
+    /// 服务器上的名称（修改版 UTF-7 编码）
+    pub mailbox: String,
+    /// 文件夹属性，例如 \Junk、\Noselect
+    pub attributes: Vec<String>,
+    /// 是否可以选择（监听）
+    pub selectable: bool,
+}
+
+impl FolderInfo {
+    /// 是否是指定的文件夹，同时接受解码后和服务器上的名称
+    pub fn matches(&self, folder: &str) -> bool {
+        self.name == folder
+            || self.mailbox == folder
+            // INBOX 不区分大小写（RFC 3501）
+            || (self.mailbox.eq_ignore_ascii_case("INBOX") && folder.eq_ignore_ascii_case("INBOX"))
+    }
+}
+
+/// 验证码扫描器：组合提取规则与验证码类型、长度约束
+///
+/// 监听邮件和离线提取（`extract` 命令）共用，不依赖IMAP配置。
+pub struct CodeScanner {
+    extractor: RuleExtractor,
+    regex: Regex,
+    code_length: Option<usize>,
+    code_type: Option<CodeType>,
+}
+
+impl CodeScanner {
+    /// 根据提取配置和监听选项创建扫描器
+    pub fn new(extract: &ExtractConfig, options: &MonitorOptions) -> Result<Self> {
+        let pattern = options
+            .code_type
+            .unwrap_or(CodeType::Numeric)
+            .pattern(options.code_length);
+        let regex = Regex::new(&pattern).context(translate("errors.create_regex"))?;
+        Ok(Self {
+            extractor: RuleExtractor::from_config(extract)?,
+            regex,
+            code_length: options.code_length,
+            code_type: options.code_type,
+        })
+    }
+
+    /// 从邮件的纯文本和HTML正文中提取候选验证码，按评分从高到低排列
+    pub fn scan(&self, message: &Message) -> Vec<ScoredCode> {
+        let sender = get_sender_address(message);
+        let mut scored = Vec::new();
+
+        if let Some(text) = get_message_text(message) {
+            let candidates = self.code_candidates(&sender, &text);
+            let context = ScoringContext {
+                text: &text,
+                html: None,
+                expected_length: self.code_length,
+            };
+            scored.extend(score_candidates(&candidates, &context));
+        }
+
+        if let Some(html) = get_message_html(message) {
+            // 不添加 **加粗** 等标记，以免影响单独成行的判断
+            let text = html2text::from_read_with_decorator(
+                html.as_bytes(),
+                80,
+                html2text::render::text_renderer::TrivialDecorator::new(),
+            );
+            let candidates = self.code_candidates(&sender, &text);
+            let context = ScoringContext {
+                text: &text,
+                html: Some(&html),
+                expected_length: self.code_length,
+            };
+            scored.extend(score_candidates(&candidates, &context));
+        }
+
+        rank(scored)
+    }
+
+    /// 从文本中找出候选验证码
+    fn code_candidates(&self, sender: &str, text: &str) -> Vec<Candidate> {
+        // 自定义规则和内置规则
+        let mut candidates = self.extractor.candidates(sender, text);
+
+        // 通用模式
+        candidates.extend(self.regex.captures_iter(text).filter_map(|cap| {
+            cap.get(1).map(|m| Candidate {
+                code: m.as_str().to_string(),
+                start: m.start(),
+                rule: "code-type".to_string(),
+                priority: 0,
+                custom: false,
+            })
+        }));
+
+        // 所有候选都需要符合用户指定的类型和长度
+        candidates.retain(|candidate| self.accepts_code(candidate));
+        candidates
+    }
+
+    /// 检查候选是否符合验证码类型和长度要求
+    ///
+    /// 用户通过参数指定的约束适用于所有候选；未指定时，内置规则默认只接受4-8位数字，
+    /// 自定义规则的结果则不受限制。
+    fn accepts_code(&self, candidate: &Candidate) -> bool {
+        let length = candidate.code.chars().count();
+        let length_ok = match self.code_length {
+            Some(expected) => length == expected,
+            None => candidate.custom || DEFAULT_CODE_LENGTH.contains(&length),
+        };
+        let type_ok = match self.code_type {
+            Some(code_type) => code_type.matches(&candidate.code),
+            None => candidate.custom || CodeType::Numeric.matches(&candidate.code),
+        };
+        length_ok && type_ok
+    }
+}
+
 /// 邮件监听器
 pub struct MailMonitor<'a> {
     config: &'a Config,
@@ -94,145 +312,252 @@ impl<'a> MailMonitor<'a> {
         Self { config, options }
     }
 
-    /// 连接到IMAP服务器
-    fn connect_imap(&self) -> Result<Session<native_tls::TlsStream<std::net::TcpStream>>> {
-        let tls = native_tls::TlsConnector::builder()
-            .min_protocol_version(Some(native_tls::Protocol::Tlsv12)) // 强制使用TLS 1.2或更高版本
+    /// 创建TLS连接器，可信任自定义CA证书或（明确开启时）自签名证书
+    fn tls_connector(&self) -> Result<tokio_native_tls::TlsConnector> {
+        let mut builder = native_tls::TlsConnector::builder();
+        builder.min_protocol_version(Some(native_tls::Protocol::Tlsv12)); // 强制使用TLS 1.2或更高版本
+
+        if let Some(ca_cert) = &self.config.smtp.ca_cert {
+            let pem = std::fs::read(ca_cert).with_context(|| {
+                translate_args("errors.read_ca_cert", &[("path", ca_cert.as_str())])
+            })?;
+            let certificate = native_tls::Certificate::from_pem(&pem).with_context(|| {
+                translate_args("errors.read_ca_cert", &[("path", ca_cert.as_str())])
+            })?;
+            builder.add_root_certificate(certificate);
+        }
+
+        if self.config.smtp.accept_invalid_certs {
+            builder.danger_accept_invalid_certs(true);
+        }
+
+        let tls = builder
             .build()
             .context(translate("errors.create_tls_connector"))?;
+        Ok(tokio_native_tls::TlsConnector::from(tls))
+    }
 
+    /// 连接并登录到IMAP服务器
+    async fn connect_imap(&self) -> Result<ImapSession> {
+        let client = self.connect().await?;
+        self.login(client).await
+    }
+
+    /// 连接到IMAP服务器并读取问候，需要时先升级为TLS
+    async fn connect(&self) -> Result<async_imap::Client<Box<dyn ImapStream>>> {
         // 连接到服务器
-        let client = imap::connect(
-            (
-                self.config.smtp.imap_server.as_str(),
-                self.config.smtp.imap_port,
+        let server = self.config.smtp.imap_server.as_str();
+        let tcp = TcpStream::connect((server, self.config.smtp.imap_port))
+            .await
+            .context(translate("errors.connect_imap"))?;
+
+        let security = self.config.smtp.security.as_str();
+        let stream: Box<dyn ImapStream> = match security {
+            "tls" => Box::new(
+                self.tls_connector()?
+                    .connect(server, tcp)
+                    .await
+                    .context(translate("errors.connect_imap"))?,
             ),
-            self.config.smtp.imap_server.as_str(),
-            &tls,
-        )
-        .context(translate("errors.connect_imap"))?;
-
-        // 登录
-        let mut imap_session = client
-            .login(&self.config.smtp.username, &self.config.smtp.password)
-            .map_err(|e| {
-                anyhow!(translate_args(
-                    "errors.imap_login_failed",
-                    &[("error", &format!("{:?}", e.0))]
-                ))
-            })?;
+            "starttls" => {
+                // 先读取明文问候，升级为TLS后服务器不会再次发送问候
+                let mut client = async_imap::Client::new(tcp);
+                client
+                    .read_response()
+                    .await
+                    .context(translate("errors.connect_imap"))?
+                    .ok_or_else(|| anyhow!(translate("errors.connect_imap")))?;
+                client
+                    .run_command_and_check_ok("STARTTLS", None)
+                    .await
+                    .context(translate("errors.starttls_failed"))?;
 
-        // 选择收件箱
-        imap_session
-            .select("INBOX")
-            .context(translate("errors.select_inbox"))?;
+                Box::new(
+                    self.tls_connector()?
+                        .connect(server, client.into_inner())
+                        .await
+                        .context(translate("errors.connect_imap"))?,
+                )
+            }
+            "none" => Box::new(tcp),
+            _ => {
+                return Err(anyhow!(translate_args(
+                    "errors.invalid_imap_security",
+                    &[("security", security)]
+                )));
+            }
+        };
+
+        let mut client = async_imap::Client::new(stream);
+        if security != "starttls" {
+            // 读取服务器问候
+            client
+                .read_response()
+                .await
+                .context(translate("errors.connect_imap"))?
+                .ok_or_else(|| anyhow!(translate("errors.connect_imap")))?;
+        }
+
+        Ok(client)
+    }
+
+    /// 登录到已连接的服务器
+    async fn login(&self, client: async_imap::Client<Box<dyn ImapStream>>) -> Result<ImapSession> {
+        let username = self.config.smtp.username.as_str();
+        let login = match self.config.smtp.auth_type.as_str() {
+            "xoauth2" => {
+                let access_token = oauth::access_token(self.config).await?;
+                client
+                    .authenticate("XOAUTH2", XOAuth2::new(username, &access_token))
+                    .await
+            }
+            _ => client.login(username, &self.config.smtp.password).await,
+        };
+        let imap_session = login.map_err(|e| {
+            anyhow!(translate_args(
+                "errors.imap_login_failed",
+                &[("error", &format!("{:?}", e.0))]
+            ))
+        })?;
 
         Ok(imap_session)
     }
 
-    /// 创建验证码提取正则表达式
-    fn create_regex(&self) -> Result<Regex> {
-        let pattern = self.options.code_type.pattern(self.options.code_length);
-        Regex::new(&pattern).context(translate("errors.create_regex"))
+    /// 要监听的文件夹，命令行参数优先于配置文件
+    fn folders(&self) -> Vec<String> {
+        if self.options.folders.is_empty() {
+            self.config.smtp.folders.clone()
+        } else {
+            self.options.folders.clone()
+        }
     }
 
-    /// 获取消息的纯文本内容
-    fn get_message_text(&self, message: &Message) -> Option<String> {
-        message.text_bodies().next().and_then(|part| {
-            std::str::from_utf8(part.contents())
-                .ok()
-                .map(|s| s.to_string())
-        })
+    /// 列出服务器上的所有文件夹
+    pub async fn list_folders(&self) -> Result<Vec<FolderInfo>> {
+        let mut session = self.connect_imap().await?;
+        let folders = self.fetch_folders(&mut session).await;
+        let _ = session.logout().await;
+        folders
     }
 
-    /// 获取消息的HTML内容
-    fn get_message_html(&self, message: &Message) -> Option<String> {
-        message.html_bodies().next().and_then(|part| {
-            std::str::from_utf8(part.contents())
-                .ok()
-                .map(|s| s.to_string())
-        })
+    async fn fetch_folders(&self, session: &mut ImapSession) -> Result<Vec<FolderInfo>> {
+        let names: Vec<_> = session
+            .list(None, Some("*"))
+            .await
+            .context(translate("errors.list_folders"))?
+            .try_collect()
+            .await
+            .context(translate("errors.list_folders"))?;
+
+        Ok(names
+            .iter()
+            .map(|name| FolderInfo {
+                name: decode_mailbox(name.name()).unwrap_or_else(|| name.name().to_string()),
+                mailbox: name.name().to_string(),
+                attributes: name.attributes().iter().map(attribute_name).collect(),
+                selectable: !name
+                    .attributes()
+                    .iter()
+                    .any(|attr| matches!(attr, NameAttribute::NoSelect)),
+            })
+            .collect())
     }
 
-    /// 从文本中提取验证码
-    fn extract_code_from_text(&self, text: &str, regex: &Regex) -> Option<String> {
-        // 针对"Enter the code XXX"或"code below: XXX"这类格式的特殊处理
-        let special_patterns = [
-            // "Enter the code 123456"格式
-            r"(?i)Enter the code[^0-9]*?([0-9]{4,8})\b",
-            // "The verification code is 123456"格式
-            r"(?i)verification code[^0-9]*?([0-9]{4,8})\b",
-            // "Your code is 123456"格式
-            r"(?i)your code[^0-9]*?([0-9]{4,8})\b",
-            // "Code: 123456"格式
-            r"(?i)code[^0-9]*?([0-9]{4,8})\b",
-            // 纯数字块 - 通常邮件中单独一行的数字很可能是验证码
-            r"(?m)^[ \t]*([0-9]{4,8})[ \t]*$",
-        ];
-
-        // 先尝试用特殊模式匹配
-        for pattern in special_patterns {
-            if let Ok(re) = Regex::new(pattern) {
-                if let Some(caps) = re.captures(text) {
-                    if let Some(m) = caps.get(1) {
-                        return Some(m.as_str().to_string());
-                    }
-                }
+    /// 检查要监听的文件夹是否都存在并记录服务器上的名称，返回第一个不存在的文件夹
+    ///
+    /// LIST 失败时不做检查，按编码后的名称 SELECT，由其报告错误。
+    async fn resolve_folders(
+        &self,
+        session: &mut ImapSession,
+        states: &mut [FolderState],
+    ) -> Option<String> {
+        let available = self.fetch_folders(session).await.ok()?;
+        for state in states.iter_mut() {
+            match available
+                .iter()
+                .find(|info| info.selectable && info.matches(&state.name))
+            {
+                Some(info) => state.mailbox = info.mailbox.clone(),
+                None => return Some(state.name.clone()),
             }
         }
+        None
+    }
 
-        // 改进匹配逻辑，避免匹配到年份或其他非验证码数字
-        // 1. 优先查找验证码标记后的数字
-        let code_markers = [
-            translate("code_markers.verification_code"),
-            translate("code_markers.code"),
-            translate("code_markers.password"),
-            translate("code_markers.check_code"),
-            translate("code_markers.dynamic_code"),
-            translate("code_markers.token"),
-            translate("code_markers.code"),
-            translate("code_markers.verification_token"),
-            translate("code_markers.dynamic_password"),
-            translate("code_markers.verification"),
-            "verification".to_string(),
-        ];
-
-        // 先尝试查找带有常见提示词的验证码
-        for marker in &code_markers {
-            let marker_regex = format!(r"{}[\s:：]*([0-9A-Za-z]{{4,8}})", regex::escape(marker));
-            if let Ok(r) = Regex::new(&marker_regex) {
-                if let Some(caps) = r.captures(text) {
-                    if let Some(m) = caps.get(1) {
-                        return Some(m.as_str().to_string());
-                    }
-                }
-            }
+    /// 检查服务器是否支持某项扩展
+    async fn has_capability(&self, session: &mut ImapSession, name: &str) -> bool {
+        match session.capabilities().await {
+            Ok(capabilities) => capabilities.has_str(name),
+            Err(_) => false,
         }
+    }
 
-        // 如果没找到带标记的验证码，再使用通用模式查找
-        regex
-            .captures(text)
-            .and_then(|cap| cap.get(1).map(|m| m.as_str().to_string()))
+    /// 检查服务器是否支持 IDLE
+    async fn supports_idle(&self, session: &mut ImapSession) -> bool {
+        self.has_capability(session, "IDLE").await
     }
 
-    /// 从邮件中提取验证码
-    fn extract_code_from_message(&self, message: &Message, regex: &Regex) -> Option<String> {
-        // 首先尝试从纯文本中提取
-        if let Some(text) = self.get_message_text(message) {
-            if let Some(code) = self.extract_code_from_text(&text, regex) {
-                return Some(code);
-            }
+    /// 按选项处理找到结果的邮件：标记已读、移动或删除
+    async fn process_message(&self, session: &mut ImapSession, uid: u32) -> Result<()> {
+        let uid = uid.to_string();
+
+        // 标记已读失败时仍然执行要求的移动或删除
+        if self.options.mark_seen
+            && let Err(e) = Self::add_flag(session, &uid, "\\Seen").await
+        {
+            eprintln!("{}: {:#}", translate("commands.watch.mark_seen_failed"), e);
         }
 
-        // 如果纯文本中没有找到，尝试从HTML中提取
-        if let Some(html) = self.get_message_html(message) {
-            let text = html2text::from_read(html.as_bytes(), 80);
-            if let Some(code) = self.extract_code_from_text(&text, regex) {
-                return Some(code);
+        match &self.options.action {
+            MessageAction::Keep => return Ok(()),
+            MessageAction::MoveTo(folder) => {
+                let folder = &encode_mailbox(folder);
+                if self.has_capability(session, "MOVE").await {
+                    session.uid_mv(&uid, folder).await?;
+                    return Ok(());
+                }
+                // 不支持 MOVE 时先复制再删除原邮件
+                session.uid_copy(&uid, folder).await?;
             }
+            MessageAction::Delete => {}
         }
 
-        None
+        Self::add_flag(session, &uid, "\\Deleted").await?;
+
+        // 支持 UIDPLUS 时只清除这一封，避免误删收件箱中其他已标记删除的邮件
+        if self.has_capability(session, "UIDPLUS").await {
+            session
+                .uid_expunge(&uid)
+                .await?
+                .try_collect::<Vec<_>>()
+                .await?;
+        } else {
+            session.expunge().await?.try_collect::<Vec<_>>().await?;
+        }
+
+        Ok(())
+    }
+
+    /// 为邮件添加标志
+    ///
+    /// `uid_store` 返回的流会吞掉服务器的 NO 响应，这里直接检查命令结果，确保失败能被发现。
+    async fn add_flag(session: &mut ImapSession, uid: &str, flag: &str) -> Result<()> {
+        session
+            .run_command_and_check_ok(format!("UID STORE {} +FLAGS.SILENT ({})", uid, flag))
+            .await?;
+        Ok(())
+    }
+
+    /// 通过 IDLE 等待新邮件，超时或收到通知后返回会话
+    async fn idle(&self, session: ImapSession, wait: Duration) -> Result<ImapSession> {
+        let mut handle = session.idle();
+        handle.init().await?;
+        {
+            let (idle_wait, _interrupt) = handle.wait_with_timeout(wait);
+            idle_wait.await?;
+        }
+        let session = handle.done().await?;
+        Ok(session)
     }
 
     /// 检查邮件是否符合过滤条件
@@ -260,39 +585,35 @@ impl<'a> MailMonitor<'a> {
         true
     }
 
-    /// 获取消息接收时间
-    #[allow(dead_code)]
-    fn get_message_date(&self, message: &Message) -> Option<DateTime<Utc>> {
-        // 尝试获取日期头信息
-        for header in message.headers() {
-            if header.name().eq_ignore_ascii_case("date") {
-                // value()直接返回HeaderValue而不是Option<T>
-                let value = header.value();
-                if let Some(date_str) = value.as_text_ref() {
-                    // 尝试从字符串解析日期
-                    if let Ok(date) = chrono::DateTime::parse_from_rfc2822(date_str) {
-                        return Some(date.with_timezone(&Utc));
-                    }
-                }
-            }
-        }
+    /// 检查邮件收件人是否符合收件人过滤条件
+    fn is_matching_recipient(&self, message: &Message) -> bool {
+        let Some(filter) = &self.options.to_filter else {
+            return true;
+        };
 
-        // 如果没有有效日期，使用当前时间
-        Some(Utc::now())
+        get_recipients(message).iter().any(|recipient| {
+            recipient_matches(filter, recipient, &self.config.alias.subaddress_separator)
+        })
     }
 
-    /// 获取邮件标题
-    fn get_message_subject(&self, message: &Message) -> String {
+    /// 获取消息发送时间
+    fn get_message_date(&self, message: &Message) -> Option<DateTime<Utc>> {
+        // mail_parser 会将 Date 头解析为日期类型
+        if let Some(date) = message.date() {
+            return DateTime::from_timestamp(date.to_timestamp(), 0);
+        }
+
+        // 回退到手动解析日期头信息
         for header in message.headers() {
-            if header.name().eq_ignore_ascii_case("subject") {
-                let value = header.value();
-                if let Some(subject) = value.as_text_ref() {
-                    return subject.to_string();
-                }
+            if header.name().eq_ignore_ascii_case("date")
+                && let Some(date_str) = header.value().as_text_ref()
+                && let Ok(date) = chrono::DateTime::parse_from_rfc2822(date_str)
+            {
+                return Some(date.with_timezone(&Utc));
             }
         }
 
-        translate("errors.no_subject")
+        None
     }
 
     /// 获取发件人
@@ -306,19 +627,18 @@ impl<'a> MailMonitor<'a> {
                 translate_args("debug.from_method_success", &[("text", from_text)])
             );
             // 尝试解析复杂的发件人格式
-            if let Some(email_start) = from_text.find('<') {
-                if let Some(email_end) = from_text.find('>') {
-                    if email_start < email_end {
-                        let name = from_text[0..email_start].trim();
-                        let email = &from_text[email_start + 1..email_end];
-                        if !name.is_empty() && !email.is_empty() {
-                            return format!("{} <{}>", name, email);
-                        } else if !email.is_empty() {
-                            return format!("<{}>", email);
-                        } else if !name.is_empty() {
-                            return name.to_string();
-                        }
-                    }
+            if let Some(email_start) = from_text.find('<')
+                && let Some(email_end) = from_text.find('>')
+                && email_start < email_end
+            {
+                let name = from_text[0..email_start].trim();
+                let email = &from_text[email_start + 1..email_end];
+                if !name.is_empty() && !email.is_empty() {
+                    return format!("{} <{}>", name, email);
+                } else if !email.is_empty() {
+                    return format!("<{}>", email);
+                } else if !name.is_empty() {
+                    return name.to_string();
                 }
             }
             return from_text.to_string();
@@ -349,19 +669,18 @@ impl<'a> MailMonitor<'a> {
                         translate_args("debug.from_text_content", &[("content", from)])
                     );
                     // 尝试解析复杂的发件人格式
-                    if let Some(email_start) = from.find('<') {
-                        if let Some(email_end) = from.find('>') {
-                            if email_start < email_end {
-                                let name = from[0..email_start].trim();
-                                let email = &from[email_start + 1..email_end];
-                                if !name.is_empty() && !email.is_empty() {
-                                    return format!("{} <{}>", name, email);
-                                } else if !email.is_empty() {
-                                    return format!("<{}>", email);
-                                } else if !name.is_empty() {
-                                    return name.to_string();
-                                }
-                            }
+                    if let Some(email_start) = from.find('<')
+                        && let Some(email_end) = from.find('>')
+                        && email_start < email_end
+                    {
+                        let name = from[0..email_start].trim();
+                        let email = &from[email_start + 1..email_end];
+                        if !name.is_empty() && !email.is_empty() {
+                            return format!("{} <{}>", name, email);
+                        } else if !email.is_empty() {
+                            return format!("<{}>", email);
+                        } else if !name.is_empty() {
+                            return name.to_string();
                         }
                     }
                     return from.to_string();
@@ -391,94 +710,484 @@ impl<'a> MailMonitor<'a> {
         translate("errors.unknown_sender")
     }
 
-    /// 等待验证码邮件
-    pub fn wait_for_code(&self) -> Result<CodeResult> {
-        let start_time = std::time::Instant::now();
-        let timeout_duration = std::time::Duration::from_secs(self.options.timeout);
-        let poll_interval = std::time::Duration::from_secs(self.options.poll_interval);
-
-        // 创建正则表达式
-        let regex = self.create_regex()?;
-
-        while start_time.elapsed() < timeout_duration {
-            // 连接IMAP服务器
-            let mut imap_session = match self.connect_imap() {
-                Ok(session) => session,
-                Err(e) => {
-                    eprintln!("{}: {}", translate("commands.watch.connecting"), e);
-                    std::thread::sleep(poll_interval);
-                    continue;
-                }
-            };
+    /// 搜索并处理未读邮件，`extract` 从某封邮件中得到结果时返回
+    async fn check_messages<T: WatchResult>(
+        &self,
+        session: &mut ImapSession,
+        extract: &impl Fn(&Message) -> Option<T>,
+        baseline: &Baseline,
+        processed: &mut HashSet<u32>,
+    ) -> Result<Option<T>> {
+        // 搜索未读邮件（SINCE 只精确到日期，且以服务器时区计算，因此多往前取一天）
+        let since = (baseline.since - chrono::Duration::days(1)).format("%d-%b-%Y");
+        let search_criteria = if let Some(ref sender) = self.options.from_filter {
+            let from_message = translate_args("commands.watch.from_filter", &[("filter", sender)]);
+            println!("{}", from_message);
+            format!("UNSEEN SINCE {} FROM \"{}\"", since, sender)
+        } else {
+            println!("{}", translate("commands.watch.searching"));
+            format!("UNSEEN SINCE {}", since)
+        };
 
-            // 搜索未读邮件
-            let search_criteria = if let Some(ref sender) = self.options.from_filter {
-                let from_message =
-                    translate_args("commands.watch.from_filter", &[("filter", sender)]);
-                println!("{}", from_message);
-                format!("UNSEEN FROM \"{}\"", sender)
-            } else {
-                println!("{}", translate("commands.watch.searching"));
-                "UNSEEN".to_string()
-            };
+        let uids = session
+            .uid_search(&search_criteria)
+            .await
+            .context(translate("errors.search_email_failed"))?;
 
-            let seq_nums = match imap_session.search(&search_criteria) {
-                Ok(nums) => nums,
-                Err(e) => {
-                    eprintln!("{}: {:?}", translate("errors.search_email_failed"), e);
-                    let _ = imap_session.logout();
-                    std::thread::sleep(poll_interval);
-                    continue;
+        // 从新到旧排序，跳过已检查过的邮件
+        let mut uids: Vec<u32> = uids
+            .into_iter()
+            .filter(|uid| !processed.contains(uid))
+            .collect();
+        uids.sort_by(|a, b| b.cmp(a));
+
+        // 处理邮件
+        for uid in uids {
+            // 监听开始前就已存在的邮件无需下载
+            let is_new = baseline.check_uid(uid);
+            if is_new == Some(false) {
+                processed.insert(uid);
+                continue;
+            }
+
+            // 使用 BODY.PEEK[] 下载，避免把共享收件箱中的其他邮件标记为已读
+            let messages: Vec<_> = session
+                .uid_fetch(uid.to_string(), "BODY.PEEK[]")
+                .await
+                .context(translate("errors.fetch_content_failed"))?
+                .try_collect()
+                .await
+                .context(translate("errors.fetch_content_failed"))?;
+            processed.insert(uid);
+
+            let result = messages.iter().find_map(|message| {
+                let mail = mail_parser::Message::parse(message.body()?)?;
+                // 跳过监听开始前的旧邮件
+                if is_new.is_none() && !baseline.accepts_date(self.get_message_date(&mail)) {
+                    return None;
                 }
-            };
+                // 跳过发给其他别名的邮件
+                if !self.is_matching_recipient(&mail) {
+                    return None;
+                }
+                extract(&mail)
+            });
+
+            if let Some(result) = result {
+                // 处理失败不影响已经得到的结果
+                if result.is_confident()
+                    && let Err(e) = self.process_message(session, uid).await
+                {
+                    eprintln!(
+                        "{}: {:#}",
+                        translate("commands.watch.post_process_failed"),
+                        e
+                    );
+                }
+                return Ok(Some(result));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// 等待验证码邮件
+    pub async fn wait_for_code(&self) -> Result<CodeResult> {
+        // 创建正则表达式和提取规则
+        let scanner = CodeScanner::new(&self.config.extract, &self.options)?;
+
+        let result = self
+            .wait_for(|mail| {
+                // 尝试从邮件中提取验证码，取评分最高的候选
+                let best = scanner.scan(mail).into_iter().next()?;
+                Some(CodeResult {
+                    code: best.code,
+                    confidence: best.confidence,
+                    // 从邮件头获取发件人信息
+                    from: self.get_message_sender(mail),
+                    // 从邮件头获取主题
+                    subject: get_message_subject(mail),
+                })
+            })
+            .await?;
+
+        result.ok_or_else(|| anyhow!(translate("commands.watch.timeout")))
+    }
 
-            if !seq_nums.is_empty() {
-                // 从新到旧排序
-                let mut seq_nums_vec: Vec<_> = seq_nums.iter().collect();
-                seq_nums_vec.sort_by(|a, b| b.cmp(a));
+    /// 等待验证链接邮件
+    pub async fn wait_for_link(&self) -> Result<LinkResult> {
+        let result = self
+            .wait_for(|mail| {
+                let link = link::rank_links(mail).into_iter().next()?;
+                Some(LinkResult {
+                    url: link.url,
+                    text: link.text,
+                    from: self.get_message_sender(mail),
+                    subject: get_message_subject(mail),
+                })
+            })
+            .await?;
 
-                // 处理邮件
-                for seq_num in seq_nums_vec {
-                    let fetch_result = match imap_session.fetch(seq_num.to_string(), "RFC822") {
-                        Ok(result) => result,
+        result.ok_or_else(|| anyhow!(translate("commands.watch_link.timeout")))
+    }
+
+    /// 等待第一封能被 `extract` 处理的新邮件，超时返回 None
+    ///
+    /// 保持同一个IMAP会话，只监听一个文件夹且服务器支持 IDLE 时等待推送通知，
+    /// 否则按轮询间隔依次检查各个文件夹；连接断开后自动重连，登录失败时直接返回错误。
+    async fn wait_for<T: WatchResult>(
+        &self,
+        extract: impl Fn(&Message) -> Option<T>,
+    ) -> Result<Option<T>> {
+        let deadline = Instant::now() + Duration::from_secs(self.options.timeout);
+        let poll_interval = Duration::from_secs(self.options.poll_interval);
+
+        let lookback = self.options.since.unwrap_or_default();
+        let since = Utc::now() - chrono::Duration::from_std(lookback)?;
+        let folders = self.folders();
+        let mut states: Vec<FolderState> = folders
+            .iter()
+            .map(|name| FolderState {
+                name: name.clone(),
+                mailbox: encode_mailbox(name),
+                baseline: Baseline {
+                    uid_validity: None,
+                    uid_next: None,
+                    since,
+                    lookback: self.options.since.is_some(),
+                },
+                processed: HashSet::new(),
+            })
+            .collect();
+
+        let mut session: Option<ImapSession> = None;
+        let mut use_idle = false;
+        let mut folders_checked = false;
+
+        while Instant::now() < deadline {
+            // 复用已有会话，断开时重新连接
+            let mut imap_session = match session.take() {
+                Some(imap_session) => imap_session,
+                None => {
+                    let client = match self.connect().await {
+                        Ok(client) => client,
                         Err(e) => {
-                            eprintln!("{}: {:?}", translate("errors.fetch_content_failed"), e);
+                            eprintln!("{}: {}", translate("commands.watch.connecting"), e);
+                            sleep(
+                                poll_interval
+                                    .min(deadline.saturating_duration_since(Instant::now())),
+                            )
+                            .await;
                             continue;
                         }
                     };
 
-                    for message in fetch_result.iter() {
-                        if let Some(body) = message.body() {
-                            if let Some(mail) = mail_parser::Message::parse(body) {
-                                // 尝试从邮件中提取验证码
-                                if let Some(code) = self.extract_code_from_message(&mail, &regex) {
-                                    // 从邮件头获取发件人信息
-                                    let from = self.get_message_sender(&mail);
-                                    // 从邮件头获取主题
-                                    let subject = self.get_message_subject(&mail);
-
-                                    // 安全地退出IMAP会话
-                                    let _ = imap_session.logout();
-
-                                    return Ok(CodeResult {
-                                        code,
-                                        from,
-                                        subject,
-                                    });
-                                }
-                            }
+                    // 缺少令牌、刷新令牌失败或服务器拒绝登录时重试没有意义，直接报错
+                    let mut imap_session = self.login(client).await?;
+
+                    // 文件夹名称写错时同样直接报错
+                    if !folders_checked {
+                        if let Some(folder) =
+                            self.resolve_folders(&mut imap_session, &mut states).await
+                        {
+                            let _ = imap_session.logout().await;
+                            return Err(anyhow!(translate_args(
+                                "errors.folder_not_found",
+                                &[("folder", &folder)]
+                            )));
                         }
+                        folders_checked = true;
+                    }
+
+                    // IDLE 只能等待当前选中的文件夹
+                    use_idle = states.len() == 1 && self.supports_idle(&mut imap_session).await;
+                    if states.len() == 1 && !use_idle {
+                        println!("{}", translate("commands.watch.idle_unsupported"));
                     }
+                    imap_session
+                }
+            };
+
+            match self
+                .check_folders(&mut imap_session, &extract, &mut states)
+                .await
+            {
+                Ok(Some(result)) => {
+                    // 安全地退出IMAP会话
+                    let _ = imap_session.logout().await;
+                    return Ok(Some(result));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("{}: {:#}", translate("commands.watch.reconnecting"), e);
+                    sleep(poll_interval.min(deadline.saturating_duration_since(Instant::now())))
+                        .await;
+                    continue;
                 }
             }
 
-            // 安全地退出IMAP会话
-            let _ = imap_session.logout();
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                let _ = imap_session.logout().await;
+                break;
+            }
 
-            // 等待下一次轮询
-            std::thread::sleep(poll_interval);
+            // 等待新邮件
+            if use_idle {
+                match self.idle(imap_session, remaining.min(IDLE_REFRESH)).await {
+                    Ok(imap_session) => session = Some(imap_session),
+                    Err(e) => {
+                        eprintln!("{}: {:#}", translate("commands.watch.reconnecting"), e);
+                    }
+                }
+            } else {
+                sleep(poll_interval.min(remaining)).await;
+                session = Some(imap_session);
+            }
         }
 
-        Err(anyhow!(translate("commands.watch.timeout")))
+        Ok(None)
+    }
+
+    /// 依次选择并检查每个文件夹
+    async fn check_folders<T: WatchResult>(
+        &self,
+        session: &mut ImapSession,
+        extract: &impl Fn(&Message) -> Option<T>,
+        states: &mut [FolderState],
+    ) -> Result<Option<T>> {
+        for state in states.iter_mut() {
+            let mailbox = session.select(&state.mailbox).await.with_context(|| {
+                translate_args("errors.select_folder", &[("folder", &state.name)])
+            })?;
+
+            // UIDVALIDITY 变化后旧的 UID 不再有效
+            if state.baseline.update(&mailbox) {
+                state.processed.clear();
+            }
+
+            if let Some(result) = self
+                .check_messages(session, extract, &state.baseline, &mut state.processed)
+                .await?
+            {
+                return Ok(Some(result));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// 单个文件夹的监听状态
+struct FolderState {
+    /// 文件夹名称
+    name: String,
+    /// 服务器上的名称（修改版 UTF-7 编码）
+    mailbox: String,
+    /// 监听开始时的基线
+    baseline: Baseline,
+    /// 已检查过的邮件 UID
+    processed: HashSet<u32>,
+}
+
+/// 监听开始时的收件箱基线，用于忽略旧邮件
+struct Baseline {
+    /// 收件箱的 UIDVALIDITY
+    uid_validity: Option<u32>,
+    /// 监听开始时的 UIDNEXT，UID 不小于该值的邮件是新送达的
+    uid_next: Option<u32>,
+    /// 早于该时间发送的邮件将被忽略
+    since: DateTime<Utc>,
+    /// 是否通过 --since 回溯接收监听开始前的邮件
+    lookback: bool,
+}
+
+impl Baseline {
+    /// 根据选中的收件箱更新基线，UIDVALIDITY 变化时返回 true
+    fn update(&mut self, mailbox: &Mailbox) -> bool {
+        match self.uid_validity {
+            None => {
+                self.uid_validity = mailbox.uid_validity;
+                self.uid_next = mailbox.uid_next;
+                false
+            }
+            Some(validity) if mailbox.uid_validity != Some(validity) => {
+                // 无法再通过 UID 判断新旧，只能依靠邮件日期
+                self.uid_validity = mailbox.uid_validity;
+                self.uid_next = None;
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    /// 通过 UID 判断邮件是否新送达，无法判断时返回 None
+    fn check_uid(&self, uid: u32) -> Option<bool> {
+        match self.uid_next {
+            Some(uid_next) if uid >= uid_next => Some(true),
+            Some(_) if !self.lookback => Some(false),
+            _ => None,
+        }
+    }
+
+    /// 通过邮件日期判断是否在基线之后，缺少日期的邮件视为旧邮件
+    fn accepts_date(&self, date: Option<DateTime<Utc>>) -> bool {
+        date.is_some_and(|date| date >= self.since)
+    }
+}
+
+/// 文件夹属性的显示名称
+fn attribute_name(attribute: &NameAttribute) -> String {
+    match attribute {
+        NameAttribute::NoInferiors => "\\Noinferiors".to_string(),
+        NameAttribute::NoSelect => "\\Noselect".to_string(),
+        NameAttribute::Marked => "\\Marked".to_string(),
+        NameAttribute::Unmarked => "\\Unmarked".to_string(),
+        NameAttribute::All => "\\All".to_string(),
+        NameAttribute::Archive => "\\Archive".to_string(),
+        NameAttribute::Drafts => "\\Drafts".to_string(),
+        NameAttribute::Flagged => "\\Flagged".to_string(),
+        NameAttribute::Junk => "\\Junk".to_string(),
+        NameAttribute::Sent => "\\Sent".to_string(),
+        NameAttribute::Trash => "\\Trash".to_string(),
+        NameAttribute::Extension(name) => name.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// 获取消息的纯文本内容
+///
+/// 只有HTML正文时 text_bodies 也会返回HTML部分，需要跳过，否则样式中的数字会被当作候选。
+fn get_message_text(message: &Message) -> Option<String> {
+    message
+        .text_bodies()
+        .find(|part| !part.is_text_html())
+        .and_then(|part| {
+            std::str::from_utf8(part.contents())
+                .ok()
+                .map(|s| s.to_string())
+        })
+}
+
+/// 获取消息的HTML内容
+fn get_message_html(message: &Message) -> Option<String> {
+    message
+        .html_bodies()
+        .find(|part| part.is_text_html())
+        .and_then(|part| {
+            std::str::from_utf8(part.contents())
+                .ok()
+                .map(|s| s.to_string())
+        })
+}
+
+/// 获取邮件标题
+pub fn get_message_subject(message: &Message) -> String {
+    for header in message.headers() {
+        if header.name().eq_ignore_ascii_case("subject") {
+            let value = header.value();
+            if let Some(subject) = value.as_text_ref() {
+                return subject.to_string();
+            }
+        }
+    }
+
+    translate("errors.no_subject")
+}
+
+/// 获取发件人邮箱地址，用于匹配提取规则
+fn get_sender_address(message: &Message) -> String {
+    let mut senders = Vec::new();
+    collect_addresses(message.from(), &mut senders);
+    senders.into_iter().next().unwrap_or_default()
+}
+
+/// 记录投递目标的头部，转发后的邮件中 To 可能不是别名本身
+const DELIVERY_HEADERS: [&str; 3] = ["delivered-to", "x-forwarded-to", "x-original-to"];
+
+/// 获取邮件的所有收件地址（To、Cc 以及投递相关头部）
+fn get_recipients(message: &Message) -> Vec<String> {
+    let mut recipients = Vec::new();
+    for value in [message.to(), message.cc()] {
+        collect_addresses(value, &mut recipients);
+    }
+
+    for header in message.headers() {
+        if DELIVERY_HEADERS
+            .iter()
+            .any(|name| header.name().eq_ignore_ascii_case(name))
+        {
+            collect_addresses(header.value(), &mut recipients);
+        }
+    }
+
+    recipients
+}
+
+/// 从地址类头部中收集邮箱地址
+fn collect_addresses(value: &HeaderValue, out: &mut Vec<String>) {
+    let mut push = |addr: &Addr| {
+        if let Some(address) = &addr.address {
+            out.push(address.to_string());
+        }
+    };
+
+    match value {
+        HeaderValue::Address(addr) => push(addr),
+        HeaderValue::AddressList(list) => list.iter().for_each(push),
+        HeaderValue::Group(group) => group.addresses.iter().for_each(push),
+        HeaderValue::GroupList(groups) => groups
+            .iter()
+            .flat_map(|group| group.addresses.iter())
+            .for_each(push),
+        HeaderValue::Text(text) => out.extend(
+            text.split(',')
+                .map(|address| address.trim().trim_start_matches('<').trim_end_matches('>'))
+                .filter(|address| !address.is_empty())
+                .map(str::to_string),
+        ),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recipients(headers: &str) -> Vec<String> {
+        let raw = format!(
+            "From: noreply@example.com\r\n{}Subject: Code\r\n\r\nBody\r\n",
+            headers
+        );
+        get_recipients(&Message::parse(raw.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn recipients_include_to_and_cc() {
+        assert_eq!(
+            recipients("To: A <a@example.com>, b@example.com\r\nCc: <c@example.com>\r\n"),
+            ["a@example.com", "b@example.com", "c@example.com"]
+        );
+    }
+
+    #[test]
+    fn recipients_include_delivery_headers() {
+        assert_eq!(
+            recipients("To: me@example.net\r\nDelivered-To: shop+x@example.com\r\n"),
+            ["me@example.net", "shop+x@example.com"]
+        );
+        assert_eq!(
+            recipients("To: list@example.org\r\nX-Forwarded-To: shop@example.com\r\n"),
+            ["list@example.org", "shop@example.com"]
+        );
+        assert_eq!(
+            recipients("X-Original-To: <shop@example.com>\r\n"),
+            ["shop@example.com"]
+        );
+    }
+
+    #[test]
+    fn recipients_without_headers() {
+        assert!(recipients("").is_empty());
     }
 }
@@ -1,2 +1,7 @@
 pub mod email;
+pub mod extractor;
+pub mod link;
 pub mod mail_monitor;
+pub mod oauth;
+pub mod sync;
+pub mod totp;
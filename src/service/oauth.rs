@@ -0,0 +1,530 @@
+use crate::api::oauth::{DevicePoll, OAuthClient, TokenResponse};
+use crate::config::{Config, config_dir, write_private_file};
+use crate::util::i18n::{translate, translate_args};
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::Utc;
+use rand::Rng;
+use rand::distributions::Alphanumeric;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// 访问令牌在到期前多久刷新（秒）
+const REFRESH_MARGIN: i64 = 60;
+
+/// 浏览器授权的最长等待时间
+const LOOPBACK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// 已保存的令牌
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredToken {
+    /// 邮箱账户（smtp.username）
+    pub account: String,
+    pub refresh_token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+    /// 访问令牌的过期时间（Unix时间戳）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+}
+
+impl StoredToken {
+    /// 访问令牌是否仍然有效
+    pub fn has_valid_access_token(&self) -> bool {
+        self.access_token.is_some()
+            && self
+                .expires_at
+                .is_some_and(|expires_at| expires_at - REFRESH_MARGIN > Utc::now().timestamp())
+    }
+
+    /// 用令牌端点的响应更新令牌，未返回新的刷新令牌时保留原来的
+    fn update(&mut self, response: TokenResponse) {
+        if let Some(refresh_token) = response.refresh_token {
+            self.refresh_token = refresh_token;
+        }
+        self.expires_at = response
+            .expires_in
+            .map(|expires_in| Utc::now().timestamp() + expires_in);
+        self.access_token = Some(response.access_token);
+    }
+}
+
+/// OAuth2 令牌文件（~/.config/cfmail/tokens.toml）
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TokenStore {
+    #[serde(default, rename = "token")]
+    pub tokens: Vec<StoredToken>,
+}
+
+impl TokenStore {
+    fn path() -> Result<PathBuf> {
+        Ok(config_dir()?.join("tokens.toml"))
+    }
+
+    /// 读取令牌文件，不存在时返回空列表
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("无法读取令牌文件: {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("解析令牌文件失败: {}", path.display()))
+    }
+
+    /// 保存令牌文件（权限0600）
+    pub fn save(&self) -> Result<()> {
+        let contents = toml::to_string(self).context("序列化令牌失败")?;
+        write_private_file(&Self::path()?, &contents)
+    }
+
+    pub fn get(&self, account: &str) -> Option<&StoredToken> {
+        self.tokens
+            .iter()
+            .find(|token| token.account.eq_ignore_ascii_case(account))
+    }
+
+    /// 添加或替换账户的令牌
+    pub fn set(&mut self, token: StoredToken) {
+        self.remove(&token.account);
+        self.tokens.push(token);
+    }
+
+    /// 删除账户的令牌，返回是否存在
+    pub fn remove(&mut self, account: &str) -> bool {
+        let before = self.tokens.len();
+        self.tokens
+            .retain(|token| !token.account.eq_ignore_ascii_case(account));
+        self.tokens.len() != before
+    }
+}
+
+/// 授权方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthFlow {
+    /// 设备码：在任意设备的浏览器中输入代码
+    Device,
+    /// 本机浏览器授权，通过本地回调地址接收授权码
+    Loopback,
+}
+
+/// 根据配置创建OAuth2客户端
+fn oauth_client(config: &Config) -> Result<OAuthClient> {
+    let oauth2 = config
+        .smtp
+        .oauth2
+        .as_ref()
+        .ok_or_else(|| anyhow!(translate("errors.oauth2_not_configured")))?;
+    OAuthClient::new(oauth2)
+}
+
+/// 完成授权并保存刷新令牌，未指定授权方式时优先使用设备码流程
+pub async fn login(config: &Config, flow: Option<AuthFlow>) -> Result<()> {
+    let client = oauth_client(config)?;
+    let flow = flow.unwrap_or(if client.supports_device_flow() {
+        AuthFlow::Device
+    } else {
+        AuthFlow::Loopback
+    });
+
+    let response = match flow {
+        AuthFlow::Device => device_login(&client).await?,
+        AuthFlow::Loopback => loopback_login(&client).await?,
+    };
+
+    let refresh_token = response
+        .refresh_token
+        .clone()
+        .ok_or_else(|| anyhow!(translate("errors.oauth2_no_refresh_token")))?;
+    let mut token = StoredToken {
+        account: config.smtp.username.clone(),
+        refresh_token,
+        access_token: None,
+        expires_at: None,
+    };
+    token.update(response);
+
+    let mut store = TokenStore::load()?;
+    store.set(token);
+    store.save()
+}
+
+/// 设备码流程
+async fn device_login(client: &OAuthClient) -> Result<TokenResponse> {
+    let device = client.request_device_code().await?;
+    println!(
+        "{}",
+        translate_args(
+            "commands.auth.device_prompt",
+            &[
+                ("url", &device.verification_uri),
+                ("code", &device.user_code),
+            ],
+        )
+    );
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(device.expires_in);
+    let mut interval = Duration::from_secs(device.interval);
+
+    while tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(interval).await;
+        match client.poll_device_token(&device.device_code).await? {
+            DevicePoll::Token(response) => return Ok(response),
+            DevicePoll::Pending => {}
+            // RFC 8628: 收到 slow_down 后轮询间隔增加5秒
+            DevicePoll::SlowDown => interval += Duration::from_secs(5),
+        }
+    }
+
+    Err(anyhow!(translate("errors.oauth2_timeout")))
+}
+
+/// 本机浏览器授权流程（授权码 + PKCE，RFC 8252）
+async fn loopback_login(client: &OAuthClient) -> Result<TokenResponse> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .context("无法监听本地回调端口")?;
+    let redirect_uri = format!("http://127.0.0.1:{}/", listener.local_addr()?.port());
+
+    let code_verifier = random_string(64);
+    let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+    let state = random_string(32);
+
+    let url = client.authorization_url(&redirect_uri, &state, &code_challenge)?;
+    println!(
+        "{}",
+        translate_args("commands.auth.browser_prompt", &[("url", &url)])
+    );
+    // 无法打开浏览器时用户可以手动访问上面的地址
+    let _ = crate::util::browser::open_url(&url);
+
+    let code = tokio::time::timeout(LOOPBACK_TIMEOUT, receive_code(&listener, &state))
+        .await
+        .map_err(|_| anyhow!(translate("errors.oauth2_timeout")))??;
+
+    client
+        .exchange_code(&code, &redirect_uri, &code_verifier)
+        .await
+}
+
+/// 等待浏览器跳转到本地回调地址，返回授权码
+///
+/// 无法解析或 state 不匹配的请求（favicon、端口扫描等）会被忽略，继续等待直到超时。
+async fn receive_code(listener: &TcpListener, state: &str) -> Result<String> {
+    loop {
+        let Ok((mut stream, _)) = listener.accept().await else {
+            continue;
+        };
+
+        // 只需要请求行
+        let mut buffer = vec![0; 8192];
+        let Ok(read) = stream.read(&mut buffer).await else {
+            continue;
+        };
+        let request = String::from_utf8_lossy(&buffer[..read]);
+        let Some(url) = request
+            .lines()
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|target| reqwest::Url::parse(&format!("http://127.0.0.1{}", target)).ok())
+        else {
+            let _ = stream
+                .write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")
+                .await;
+            continue;
+        };
+
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.to_string())
+        };
+
+        // 只处理携带本次 state 的回调
+        if param("state").as_deref() != Some(state) {
+            let _ = stream
+                .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                .await;
+            continue;
+        }
+
+        let result = match (param("code"), param("error")) {
+            (Some(code), None) => Ok(code),
+            (_, error) => Err(anyhow!(translate_args(
+                "errors.oauth2_denied",
+                &[("error", error.as_deref().unwrap_or_default())]
+            ))),
+        };
+
+        // 页面只显示固定文本，不回显请求中的参数
+        let message = match &result {
+            Ok(_) => translate("commands.auth.browser_done"),
+            Err(_) => translate("commands.auth.browser_failed"),
+        };
+        let body = format!(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"></head><body><p>{}</p></body></html>",
+            message
+        );
+        let _ = stream
+            .write_all(
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .as_bytes(),
+            )
+            .await;
+
+        return result;
+    }
+}
+
+/// 获取IMAP访问令牌，过期时使用刷新令牌更新
+pub async fn access_token(config: &Config) -> Result<String> {
+    let mut store = TokenStore::load()?;
+    let mut token = store
+        .get(&config.smtp.username)
+        .cloned()
+        .ok_or_else(|| anyhow!(translate("errors.oauth2_not_logged_in")))?;
+
+    if let Some(access_token) = token.access_token.as_ref()
+        && token.has_valid_access_token()
+    {
+        return Ok(access_token.clone());
+    }
+
+    let response = oauth_client(config)?
+        .refresh(&token.refresh_token)
+        .await
+        .context(translate("errors.oauth2_refresh_failed"))?;
+    token.update(response);
+    let access_token = token.access_token.clone().unwrap_or_default();
+
+    store.set(token);
+    store.save()?;
+    Ok(access_token)
+}
+
+/// 生成PKCE和state使用的随机字符串
+fn random_string(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+/// XOAUTH2 SASL 认证
+pub struct XOAuth2 {
+    user: String,
+    access_token: String,
+    sent: bool,
+}
+
+impl XOAuth2 {
+    pub fn new(user: &str, access_token: &str) -> Self {
+        Self {
+            user: user.to_string(),
+            access_token: access_token.to_string(),
+            sent: false,
+        }
+    }
+}
+
+impl async_imap::Authenticator for XOAuth2 {
+    type Response = String;
+
+    fn process(&mut self, _challenge: &[u8]) -> Self::Response {
+        // 认证失败时服务器会返回包含错误信息的质询，回复空行结束认证
+        if self.sent {
+            return String::new();
+        }
+        self.sent = true;
+        format!(
+            "user={}\x01auth=Bearer {}\x01\x01",
+            self.user, self.access_token
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_imap::Authenticator;
+    use tokio::net::TcpStream;
+
+    fn token(account: &str) -> StoredToken {
+        StoredToken {
+            account: account.to_string(),
+            refresh_token: "refresh".to_string(),
+            access_token: Some("access".to_string()),
+            expires_at: Some(Utc::now().timestamp() + 3600),
+        }
+    }
+
+    #[test]
+    fn xoauth2_initial_response() {
+        let mut auth = XOAuth2::new("user@example.com", "ya29.token");
+        assert_eq!(
+            auth.process(b""),
+            "user=user@example.com\x01auth=Bearer ya29.token\x01\x01"
+        );
+        // 认证失败后的质询以空行回复
+        assert_eq!(auth.process(b"eyJzdGF0dXMiOiI0MDAifQ=="), "");
+    }
+
+    #[test]
+    fn token_store_round_trip() {
+        let mut store = TokenStore::default();
+        store.set(token("user@example.com"));
+        store.set(StoredToken {
+            access_token: None,
+            expires_at: None,
+            ..token("other@example.com")
+        });
+
+        let loaded: TokenStore = toml::from_str(&toml::to_string(&store).unwrap()).unwrap();
+        let user = loaded.get("USER@example.com").unwrap();
+        assert_eq!(user.refresh_token, "refresh");
+        assert_eq!(user.access_token.as_deref(), Some("access"));
+        assert!(user.has_valid_access_token());
+        assert!(
+            !loaded
+                .get("other@example.com")
+                .unwrap()
+                .has_valid_access_token()
+        );
+    }
+
+    #[test]
+    fn token_store_set_replaces_account() {
+        let mut store = TokenStore::default();
+        store.set(token("user@example.com"));
+        store.set(StoredToken {
+            refresh_token: "new".to_string(),
+            ..token("User@Example.com")
+        });
+
+        assert_eq!(store.tokens.len(), 1);
+        assert_eq!(store.get("user@example.com").unwrap().refresh_token, "new");
+        assert!(store.remove("user@example.com"));
+        assert!(!store.remove("user@example.com"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn token_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("cfmail-token-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tokens.toml");
+        // 已存在的宽松权限文件也会被收紧
+        std::fs::write(&path, "").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let mut store = TokenStore::default();
+        store.set(token("user@example.com"));
+        write_private_file(&path, &toml::to_string(&store).unwrap()).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        let loaded: TokenStore = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(loaded.get("user@example.com").is_some());
+    }
+
+    #[test]
+    fn expired_access_token_needs_refresh() {
+        let mut expired = token("user@example.com");
+        expired.expires_at = Some(Utc::now().timestamp() - 10);
+        assert!(!expired.has_valid_access_token());
+
+        // 即将过期的令牌同样需要刷新
+        let mut expiring = token("user@example.com");
+        expiring.expires_at = Some(Utc::now().timestamp() + REFRESH_MARGIN / 2);
+        assert!(!expiring.has_valid_access_token());
+    }
+
+    #[test]
+    fn update_keeps_refresh_token_when_not_returned() {
+        let mut stored = token("user@example.com");
+        stored.update(TokenResponse {
+            access_token: "new-access".to_string(),
+            refresh_token: None,
+            expires_in: Some(3600),
+        });
+        assert_eq!(stored.refresh_token, "refresh");
+        assert_eq!(stored.access_token.as_deref(), Some("new-access"));
+        assert!(stored.has_valid_access_token());
+
+        stored.update(TokenResponse {
+            access_token: "newer".to_string(),
+            refresh_token: Some("rotated".to_string()),
+            expires_in: None,
+        });
+        assert_eq!(stored.refresh_token, "rotated");
+        // 没有有效期时无法判断，下次使用前重新刷新
+        assert!(!stored.has_valid_access_token());
+    }
+
+    /// 向回调地址发送请求，返回响应
+    async fn get(port: u16, target: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        stream
+            .write_all(format!("GET {} HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n", target).as_bytes())
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn receive_code_ignores_wrong_state() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let client = tokio::spawn(async move {
+            let wrong = get(port, "/?code=evil&state=other").await;
+            let stray = get(port, "/favicon.ico").await;
+            let right = get(port, "/?code=good&state=expected").await;
+            (wrong, stray, right)
+        });
+
+        let code = receive_code(&listener, "expected").await.unwrap();
+        let (wrong, stray, right) = client.await.unwrap();
+
+        assert_eq!(code, "good");
+        assert!(wrong.starts_with("HTTP/1.1 404"), "{}", wrong);
+        assert!(stray.starts_with("HTTP/1.1 404"), "{}", stray);
+        assert!(right.starts_with("HTTP/1.1 200"), "{}", right);
+    }
+
+    #[tokio::test]
+    async fn receive_code_does_not_echo_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let client = tokio::spawn(async move {
+            get(
+                port,
+                "/?state=expected&error=%3Cscript%3Ealert(1)%3C/script%3E",
+            )
+            .await
+        });
+
+        assert!(receive_code(&listener, "expected").await.is_err());
+        let response = client.await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(!response.contains("<script>"), "{}", response);
+    }
+}
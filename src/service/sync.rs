@@ -0,0 +1,412 @@
+use crate::api::cloudflare::models::{
+    EmailRoute, EmailRouteAction, EmailRouteCreate, EmailRouteMatcher, managed_rule_name,
+};
+use crate::config::Config;
+use crate::service::email::validate_alias;
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// 别名清单文件
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    #[serde(default, rename = "alias")]
+    pub aliases: Vec<AliasSpec>,
+}
+
+/// 清单中的单个别名定义
+#[derive(Debug, Deserialize)]
+pub struct AliasSpec {
+    /// 别名地址
+    pub address: String,
+    /// 转发目标（forward 为邮箱，worker 为 Worker 名称），为空时使用配置中的目标邮箱
+    #[serde(default)]
+    pub destinations: Vec<String>,
+    /// 动作类型: forward / drop / worker
+    #[serde(default = "default_action")]
+    pub action: String,
+    /// 是否启用
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_action() -> String {
+    "forward".to_string()
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// 别名路由的可比较状态
+///
+/// 目标地址没有先后之分，构造时排序去重，以免顺序不同被当作变更。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteState {
+    pub address: String,
+    pub action: String,
+    pub destinations: Vec<String>,
+    pub enabled: bool,
+}
+
+impl RouteState {
+    /// 从Cloudflare路由规则中读取状态
+    fn from_route(route: &EmailRoute) -> Option<Self> {
+        let address = route.address()?.to_lowercase();
+        let (action, destinations) = match route.actions.first() {
+            Some(action) => (action.action_type.clone(), action.value.clone()),
+            None => ("drop".to_string(), Vec::new()),
+        };
+
+        Some(Self {
+            address,
+            action,
+            destinations: normalize_destinations(destinations),
+            enabled: route.enabled,
+        })
+    }
+
+    /// 转换为Cloudflare路由规则请求
+    pub fn to_rule(&self) -> EmailRouteCreate {
+        EmailRouteCreate {
+            matchers: vec![EmailRouteMatcher {
+                matcher_type: "literal".to_string(),
+                field: Some("to".to_string()),
+                value: Some(self.address.clone()),
+            }],
+            actions: vec![EmailRouteAction {
+                action_type: self.action.clone(),
+                value: self.destinations.clone(),
+            }],
+            enabled: self.enabled,
+            name: Some(managed_rule_name(&self.address)),
+        }
+    }
+}
+
+/// 排序并去除重复的目标地址
+fn normalize_destinations(mut destinations: Vec<String>) -> Vec<String> {
+    destinations.sort();
+    destinations.dedup();
+    destinations
+}
+
+/// 单项变更
+#[derive(Debug)]
+pub enum Change {
+    /// 新建规则
+    Create(RouteState),
+    /// 更新已有规则
+    Update {
+        id: String,
+        before: RouteState,
+        after: RouteState,
+    },
+    /// 删除规则
+    Delete { id: String, state: RouteState },
+}
+
+/// 同步计划
+#[derive(Debug, Default)]
+pub struct Plan {
+    /// 需要执行的变更
+    pub changes: Vec<Change>,
+    /// 清单中已存在但不受 cfmail 管理的地址（不会被修改）
+    pub unmanaged: Vec<String>,
+}
+
+impl Plan {
+    /// 统计新建、更新、删除的数量
+    pub fn counts(&self) -> (usize, usize, usize) {
+        self.changes
+            .iter()
+            .fold((0, 0, 0), |(c, u, d), change| match change {
+                Change::Create(_) => (c + 1, u, d),
+                Change::Update { .. } => (c, u + 1, d),
+                Change::Delete { .. } => (c, u, d + 1),
+            })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// 读取别名清单文件
+pub fn load_manifest(path: &Path) -> Result<Manifest> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("无法读取清单文件: {}", path.display()))?;
+
+    toml::from_str(&contents).with_context(|| format!("解析清单文件失败: {}", path.display()))
+}
+
+/// 将清单转换为期望状态，并检查其合法性
+pub fn desired_state(config: &Config, manifest: &Manifest) -> Result<Vec<RouteState>> {
+    let mut seen = HashSet::new();
+    let mut states = Vec::with_capacity(manifest.aliases.len());
+
+    for spec in &manifest.aliases {
+        let address = validate_alias(config, &spec.address)?;
+        if !seen.insert(address.clone()) {
+            return Err(anyhow!("清单错误: 别名重复定义: {}", address));
+        }
+
+        let destinations = match spec.action.as_str() {
+            "forward" if spec.destinations.is_empty() => vec![config.email.target_email.clone()],
+            "forward" | "worker" if !spec.destinations.is_empty() => spec.destinations.clone(),
+            "worker" => {
+                return Err(anyhow!(
+                    "清单错误: {} 使用 worker 动作但未指定 Worker",
+                    address
+                ));
+            }
+            "drop" => Vec::new(),
+            other => {
+                return Err(anyhow!(
+                    "清单错误: {} 使用了不支持的动作: {}",
+                    address,
+                    other
+                ));
+            }
+        };
+
+        states.push(RouteState {
+            address,
+            action: spec.action.clone(),
+            destinations: normalize_destinations(destinations),
+            enabled: spec.enabled,
+        });
+    }
+
+    Ok(states)
+}
+
+/// 比较期望状态与Cloudflare中的现有规则，生成同步计划
+///
+/// 只有由 cfmail 创建的规则会被更新或删除。
+pub fn plan(desired: &[RouteState], current: &[EmailRoute]) -> Plan {
+    let mut plan = Plan::default();
+
+    let mut managed: HashMap<String, (&EmailRoute, RouteState)> = HashMap::new();
+    let mut unmanaged: HashSet<String> = HashSet::new();
+    for route in current {
+        if let Some(state) = RouteState::from_route(route) {
+            if route.is_managed() {
+                managed.insert(state.address.clone(), (route, state));
+            } else {
+                unmanaged.insert(state.address);
+            }
+        }
+    }
+
+    for state in desired {
+        match managed.remove(&state.address) {
+            Some((route, before)) => {
+                if before != *state {
+                    plan.changes.push(Change::Update {
+                        id: route.id.clone(),
+                        before,
+                        after: state.clone(),
+                    });
+                }
+            }
+            None if unmanaged.contains(&state.address) => {
+                plan.unmanaged.push(state.address.clone());
+            }
+            None => plan.changes.push(Change::Create(state.clone())),
+        }
+    }
+
+    // 剩余的受管理规则不在清单中，需要删除
+    let mut removed: Vec<_> = managed.into_values().collect();
+    removed.sort_by(|a, b| a.1.address.cmp(&b.1.address));
+    for (route, state) in removed {
+        plan.changes.push(Change::Delete {
+            id: route.id.clone(),
+            state,
+        });
+    }
+
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[cloudflare]
+api_key = "test"
+api_token = ""
+zone_id = "test"
+email = "test@example.com"
+
+[email]
+domain = "example.com"
+target_email = "me@example.net"
+
+[alias]
+prefix_mode = "random"
+custom_prefixes = []
+random_length = 8
+random_charset = "alphabetic"
+
+[smtp]
+username = "user"
+password = "pass"
+imap_server = "127.0.0.1"
+imap_port = 993
+smtp_server = "127.0.0.1"
+smtp_port = 587
+"#;
+
+    fn manifest(addresses: &[&str]) -> Manifest {
+        Manifest {
+            aliases: addresses
+                .iter()
+                .map(|address| AliasSpec {
+                    address: address.to_string(),
+                    destinations: Vec::new(),
+                    action: default_action(),
+                    enabled: true,
+                })
+                .collect(),
+        }
+    }
+
+    fn state(address: &str, destination: &str) -> RouteState {
+        RouteState {
+            address: address.to_string(),
+            action: "forward".to_string(),
+            destinations: vec![destination.to_string()],
+            enabled: true,
+        }
+    }
+
+    fn route(id: &str, address: &str, destination: &str, managed: bool) -> EmailRoute {
+        EmailRoute {
+            id: id.to_string(),
+            matchers: vec![EmailRouteMatcher {
+                matcher_type: "literal".to_string(),
+                field: Some("to".to_string()),
+                value: Some(address.to_string()),
+            }],
+            actions: vec![EmailRouteAction {
+                action_type: "forward".to_string(),
+                value: vec![destination.to_string()],
+            }],
+            enabled: true,
+            name: if managed {
+                managed_rule_name(address)
+            } else {
+                "manual".to_string()
+            },
+        }
+    }
+
+    #[test]
+    fn desired_state_rejects_invalid_addresses() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+
+        let desired = desired_state(&config, &manifest(&["Shop@Example.com"])).unwrap();
+        assert_eq!(desired, vec![state("shop@example.com", "me@example.net")]);
+
+        assert!(desired_state(&config, &manifest(&[""])).is_err());
+        assert!(desired_state(&config, &manifest(&["@example.com"])).is_err());
+        assert!(desired_state(&config, &manifest(&["shop@other.com"])).is_err());
+        assert!(
+            desired_state(
+                &config,
+                &manifest(&["shop@example.com", " SHOP@example.com"])
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn plan_creates_missing_routes() {
+        let desired = [state("a@example.com", "me@example.net")];
+        let plan = plan(&desired, &[]);
+
+        assert_eq!(plan.counts(), (1, 0, 0));
+        assert!(matches!(&plan.changes[0], Change::Create(s) if s.address == "a@example.com"));
+    }
+
+    #[test]
+    fn plan_updates_changed_managed_routes() {
+        let desired = [
+            state("a@example.com", "new@example.net"),
+            state("b@example.com", "me@example.net"),
+        ];
+        let current = [
+            route("1", "a@example.com", "old@example.net", true),
+            route("2", "b@example.com", "me@example.net", true),
+        ];
+        let plan = plan(&desired, &current);
+
+        // 未变化的规则不会出现在计划中
+        assert_eq!(plan.counts(), (0, 1, 0));
+        match &plan.changes[0] {
+            Change::Update { id, before, after } => {
+                assert_eq!(id, "1");
+                assert_eq!(before.destinations, vec!["old@example.net"]);
+                assert_eq!(after.destinations, vec!["new@example.net"]);
+            }
+            other => panic!("unexpected change: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn plan_ignores_destination_order() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let mut manifest = manifest(&["a@example.com"]);
+        manifest.aliases[0].destinations =
+            vec!["b@example.net".to_string(), "a@example.net".to_string()];
+        let desired = desired_state(&config, &manifest).unwrap();
+
+        let mut current = route("1", "a@example.com", "a@example.net", true);
+        current.actions[0].value = vec!["a@example.net".to_string(), "b@example.net".to_string()];
+        assert!(plan(&desired, &[current.clone()]).is_empty());
+
+        current.actions[0].value.reverse();
+        assert!(plan(&desired, &[current.clone()]).is_empty());
+
+        // 目标地址确实变化时仍然更新
+        current.actions[0].value = vec!["a@example.net".to_string()];
+        assert_eq!(plan(&desired, &[current]).counts(), (0, 1, 0));
+    }
+
+    #[test]
+    fn plan_deletes_managed_routes_missing_from_manifest() {
+        let current = [
+            route("2", "b@example.com", "me@example.net", true),
+            route("1", "a@example.com", "me@example.net", true),
+        ];
+        let plan = plan(&[], &current);
+
+        assert_eq!(plan.counts(), (0, 0, 2));
+        let deleted: Vec<&str> = plan
+            .changes
+            .iter()
+            .map(|change| match change {
+                Change::Delete { id, .. } => id.as_str(),
+                other => panic!("unexpected change: {:?}", other),
+            })
+            .collect();
+        assert_eq!(deleted, ["1", "2"]);
+    }
+
+    #[test]
+    fn plan_leaves_unmanaged_routes_alone() {
+        let desired = [state("a@example.com", "me@example.net")];
+        let current = [
+            route("1", "a@example.com", "other@example.net", false),
+            route("2", "b@example.com", "me@example.net", false),
+        ];
+        let plan = plan(&desired, &current);
+
+        assert!(plan.is_empty());
+        assert_eq!(plan.unmanaged, vec!["a@example.com"]);
+    }
+}
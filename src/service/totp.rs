@@ -0,0 +1,391 @@
+use crate::config::{config_dir, write_private_file};
+use anyhow::{Context, Result, anyhow};
+use hmac::digest::KeyInit;
+use hmac::{Hmac, Mac};
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// TOTP 密钥
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TotpEntry {
+    /// 标签，通常是别名地址或服务名称
+    pub label: String,
+    /// Base32 编码的密钥
+    pub secret: String,
+    /// 发行方
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    /// 验证码位数
+    #[serde(default = "default_digits")]
+    pub digits: u32,
+    /// 时间步长（秒）
+    #[serde(default = "default_period")]
+    pub period: u64,
+    /// 哈希算法: SHA1 / SHA256 / SHA512
+    #[serde(default = "default_algorithm")]
+    pub algorithm: String,
+}
+
+fn default_digits() -> u32 {
+    6
+}
+
+fn default_period() -> u64 {
+    30
+}
+
+fn default_algorithm() -> String {
+    "SHA1".to_string()
+}
+
+impl TotpEntry {
+    /// 从 Base32 密钥或 otpauth:// URI 创建
+    pub fn parse(label: &str, input: &str) -> Result<Self> {
+        let input = input.trim();
+        let mut entry = Self {
+            label: label.to_string(),
+            secret: String::new(),
+            issuer: None,
+            digits: default_digits(),
+            period: default_period(),
+            algorithm: default_algorithm(),
+        };
+
+        if input.to_lowercase().starts_with("otpauth://") {
+            let url = reqwest::Url::parse(input).context("无效的 otpauth URI")?;
+            if url.host_str() != Some("totp") {
+                return Err(anyhow!("只支持 otpauth://totp 类型的 URI"));
+            }
+
+            // 标签格式为 "发行方:账户"
+            let path = percent_decode_str(url.path().trim_start_matches('/')).decode_utf8_lossy();
+            if let Some((issuer, _)) = path.split_once(':') {
+                entry.issuer = Some(issuer.trim().to_string());
+            }
+
+            for (key, value) in url.query_pairs() {
+                match key.to_lowercase().as_str() {
+                    "secret" => entry.secret = value.to_string(),
+                    "issuer" => entry.issuer = Some(value.to_string()),
+                    "digits" => entry.digits = value.parse().context("无效的 digits 参数")?,
+                    "period" => entry.period = value.parse().context("无效的 period 参数")?,
+                    "algorithm" => entry.algorithm = value.to_uppercase(),
+                    _ => {}
+                }
+            }
+        } else {
+            entry.secret = input.to_string();
+        }
+
+        entry.secret = normalize_secret(&entry.secret);
+        entry.validate()?;
+        Ok(entry)
+    }
+
+    /// 检查参数是否有效
+    pub fn validate(&self) -> Result<()> {
+        if self.secret.is_empty() {
+            return Err(anyhow!("TOTP 密钥不能为空"));
+        }
+        if base32_decode(&self.secret)?.is_empty() {
+            // 单个 Base32 字符不足一个字节，解码后是空密钥
+            return Err(anyhow!("TOTP 密钥太短: 解码后为空"));
+        }
+        if !(6..=8).contains(&self.digits) {
+            return Err(anyhow!("TOTP 位数必须在 6 到 8 之间"));
+        }
+        if self.period == 0 {
+            return Err(anyhow!("TOTP 时间步长必须大于 0"));
+        }
+        if !["SHA1", "SHA256", "SHA512"].contains(&self.algorithm.as_str()) {
+            return Err(anyhow!("不支持的 TOTP 算法: {}", self.algorithm));
+        }
+        Ok(())
+    }
+
+    /// 计算指定时间的验证码（RFC 6238）
+    pub fn code_at(&self, unix_time: u64) -> Result<String> {
+        let key = base32_decode(&self.secret)?;
+        let counter = (unix_time / self.period).to_be_bytes();
+
+        let digest = match self.algorithm.as_str() {
+            "SHA256" => hmac_digest::<Hmac<sha2::Sha256>>(&key, &counter),
+            "SHA512" => hmac_digest::<Hmac<sha2::Sha512>>(&key, &counter),
+            _ => hmac_digest::<Hmac<sha1::Sha1>>(&key, &counter),
+        };
+
+        // 动态截断（RFC 4226）
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+        let code = binary as u64 % 10u64.pow(self.digits);
+
+        Ok(format!("{:0width$}", code, width = self.digits as usize))
+    }
+
+    /// 计算当前验证码，同时返回剩余有效秒数
+    pub fn current_code(&self) -> Result<(String, u64)> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .context("系统时间早于1970年")?
+            .as_secs();
+        let remaining = self.period - now % self.period;
+        Ok((self.code_at(now)?, remaining))
+    }
+}
+
+fn hmac_digest<M: Mac + KeyInit>(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = <M as KeyInit>::new_from_slice(key).expect("HMAC可以接受任意长度的密钥");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// 去除密钥中的空格、连字符和填充，并转为大写
+fn normalize_secret(secret: &str) -> String {
+    secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-' && *c != '=')
+        .collect::<String>()
+        .to_uppercase()
+}
+
+/// Base32 解码（RFC 4648），不区分大小写，末尾填充可省略
+fn base32_decode(input: &str) -> Result<Vec<u8>> {
+    let input = input.trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(input.len() * 5 / 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for c in input.chars() {
+        let value = match c.to_ascii_uppercase() {
+            upper @ 'A'..='Z' => upper as u32 - 'A' as u32,
+            digit @ '2'..='7' => digit as u32 - '2' as u32 + 26,
+            _ => return Err(anyhow!("TOTP 密钥不是有效的 Base32: 包含字符 {}", c)),
+        };
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// TOTP 密钥文件（~/.config/cfmail/totp.toml）
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TotpStore {
+    #[serde(default, rename = "totp")]
+    pub entries: Vec<TotpEntry>,
+}
+
+impl TotpStore {
+    fn path() -> Result<PathBuf> {
+        Ok(config_dir()?.join("totp.toml"))
+    }
+
+    /// 读取密钥文件并检查每个密钥的参数，文件不存在时返回空列表
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("无法读取TOTP密钥文件: {}", path.display()))?;
+        let store: Self = toml::from_str(&contents)
+            .with_context(|| format!("解析TOTP密钥文件失败: {}", path.display()))?;
+
+        // 文件可能被手动编辑，避免无效参数在计算时出错
+        for entry in &store.entries {
+            entry.validate().with_context(|| {
+                format!("TOTP密钥文件中的 {} 无效: {}", entry.label, path.display())
+            })?;
+        }
+        Ok(store)
+    }
+
+    /// 保存密钥文件（权限0600）
+    pub fn save(&self) -> Result<()> {
+        let contents = toml::to_string(self).context("序列化TOTP密钥失败")?;
+        write_private_file(&Self::path()?, &contents)
+    }
+
+    pub fn get(&self, label: &str) -> Option<&TotpEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.label.eq_ignore_ascii_case(label))
+    }
+
+    /// 添加密钥，标签已存在时返回错误
+    pub fn add(&mut self, entry: TotpEntry) -> Result<()> {
+        if self.get(&entry.label).is_some() {
+            return Err(anyhow!("TOTP 标签已存在: {}", entry.label));
+        }
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// 删除密钥，返回是否存在
+    pub fn remove(&mut self, label: &str) -> bool {
+        let before = self.entries.len();
+        self.entries
+            .retain(|entry| !entry.label.eq_ignore_ascii_case(label));
+        self.entries.len() != before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 6238 附录B的测试时间
+    const TIMES: [u64; 6] = [
+        59,
+        1111111109,
+        1111111111,
+        1234567890,
+        2000000000,
+        20000000000,
+    ];
+
+    fn rfc_entry(algorithm: &str, secret: &str) -> TotpEntry {
+        TotpEntry {
+            label: "rfc6238".to_string(),
+            secret: secret.to_string(),
+            issuer: None,
+            digits: 8,
+            period: 30,
+            algorithm: algorithm.to_string(),
+        }
+    }
+
+    fn assert_codes(entry: &TotpEntry, expected: [&str; 6]) {
+        for (time, code) in TIMES.iter().zip(expected) {
+            assert_eq!(entry.code_at(*time).unwrap(), code, "time {}", time);
+        }
+    }
+
+    #[test]
+    fn rfc6238_sha1() {
+        // "12345678901234567890"
+        let entry = rfc_entry("SHA1", "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_codes(
+            &entry,
+            [
+                "94287082", "07081804", "14050471", "89005924", "69279037", "65353130",
+            ],
+        );
+    }
+
+    #[test]
+    fn rfc6238_sha256() {
+        // "12345678901234567890123456789012"
+        let entry = rfc_entry(
+            "SHA256",
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA====",
+        );
+        assert_codes(
+            &entry,
+            [
+                "46119246", "68084774", "67062674", "91819424", "90698825", "77737706",
+            ],
+        );
+    }
+
+    #[test]
+    fn rfc6238_sha512() {
+        // "1234567890" 重复至64字节
+        let entry = rfc_entry(
+            "SHA512",
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNA=",
+        );
+        assert_codes(
+            &entry,
+            [
+                "90693936", "25091201", "99943326", "93441116", "38618901", "47863826",
+            ],
+        );
+    }
+
+    #[test]
+    fn base32_decode_handles_padding() {
+        assert_eq!(base32_decode("MZXW6YQ=").unwrap(), b"foob");
+        assert_eq!(base32_decode("MZXW6YQ").unwrap(), b"foob");
+        assert_eq!(base32_decode("").unwrap(), b"");
+    }
+
+    #[test]
+    fn base32_decode_accepts_lowercase() {
+        assert_eq!(base32_decode("mzxw6yq=").unwrap(), b"foob");
+        assert_eq!(base32_decode("MzXw6Yq").unwrap(), b"foob");
+    }
+
+    #[test]
+    fn base32_decode_rejects_invalid_chars() {
+        assert!(base32_decode("MZXW6YQ1").is_err());
+        assert!(base32_decode("MZXW 6YQ").is_err());
+        assert!(base32_decode("MZ=XW6YQ").is_err());
+    }
+
+    #[test]
+    fn validate_rejects_empty_key() {
+        // 单个字符只有5位，解码后没有完整字节
+        assert!(TotpEntry::parse("short", "A").is_err());
+        assert!(TotpEntry::parse("short", "AB").is_ok());
+        assert!(TotpEntry::parse("short", "otpauth://totp/x?secret=A").is_err());
+    }
+
+    #[test]
+    fn parse_decodes_otpauth_label() {
+        let entry = TotpEntry::parse(
+            "github",
+            "otpauth://totp/ACME%20Co%3Ame@example.com?secret=jbsw%20y3dp&digits=8&algorithm=sha256",
+        )
+        .unwrap();
+        assert_eq!(entry.issuer.as_deref(), Some("ACME Co"));
+        assert_eq!(entry.secret, "JBSWY3DP");
+        assert_eq!(entry.digits, 8);
+        assert_eq!(entry.algorithm, "SHA256");
+
+        // issuer 参数优先于标签中的发行方
+        let entry = TotpEntry::parse(
+            "x",
+            "otpauth://totp/Old:me?issuer=New%20Inc&secret=JBSWY3DP",
+        )
+        .unwrap();
+        assert_eq!(entry.issuer.as_deref(), Some("New Inc"));
+    }
+
+    #[test]
+    fn parse_keeps_malformed_percent_sequences() {
+        let entry = TotpEntry::parse("x", "otpauth://totp/A%zz%4:me?secret=JBSWY3DP").unwrap();
+        assert_eq!(entry.issuer.as_deref(), Some("A%zz%4"));
+
+        let entry = TotpEntry::parse("x", "otpauth://totp/%E4%BE%8B%:me?secret=JBSWY3DP").unwrap();
+        assert_eq!(entry.issuer.as_deref(), Some("例%"));
+    }
+
+    #[test]
+    fn validate_rejects_invalid_parameters() {
+        let mut entry = rfc_entry("SHA1", "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert!(entry.validate().is_ok());
+
+        entry.period = 0;
+        assert!(entry.validate().is_err());
+
+        entry.period = 30;
+        entry.digits = 9;
+        assert!(entry.validate().is_err());
+        entry.digits = 5;
+        assert!(entry.validate().is_err());
+    }
+}
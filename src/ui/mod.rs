@@ -1,3 +1,4 @@
+use crate::service::sync::{Change, Plan, RouteState};
 use crate::util::i18n;
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
@@ -197,7 +198,6 @@ pub fn print_result_box(title: &str, value: &str) {
 }
 
 /// 打印表格
-#[allow(dead_code)]
 pub fn print_table<T: ToString>(headers: &[&str], rows: &[Vec<T>]) {
     // 创建表格
     let mut table = Table::new();
@@ -265,3 +265,79 @@ pub fn print_aliases_table(aliases: &[String]) {
     table.printstd();
     println!();
 }
+
+/// 打印同步计划（类似 Terraform 的 plan 输出）
+pub fn print_sync_plan(plan: &Plan) {
+    print_section_title(&i18n::translate("commands.apply.plan_title"));
+
+    for change in &plan.changes {
+        match change {
+            Change::Create(state) => {
+                println!("  {} {}", "+".green().bold(), state.address.green());
+                print_route_fields(state);
+            }
+            Change::Update { before, after, .. } => {
+                println!("  {} {}", "~".yellow().bold(), after.address.yellow());
+                if before.action != after.action {
+                    print_field_change("action", &before.action, &after.action);
+                }
+                if before.destinations != after.destinations {
+                    print_field_change(
+                        "destinations",
+                        &before.destinations.join(", "),
+                        &after.destinations.join(", "),
+                    );
+                }
+                if before.enabled != after.enabled {
+                    print_field_change(
+                        "enabled",
+                        &before.enabled.to_string(),
+                        &after.enabled.to_string(),
+                    );
+                }
+            }
+            Change::Delete { state, .. } => {
+                println!("  {} {}", "-".red().bold(), state.address.red());
+            }
+        }
+    }
+
+    let (create, update, delete) = plan.counts();
+    println!();
+    println!(
+        "{}",
+        i18n::translate_args(
+            "commands.apply.summary",
+            &[
+                ("create", &create.to_string()),
+                ("update", &update.to_string()),
+                ("delete", &delete.to_string()),
+            ],
+        )
+        .bold()
+    );
+}
+
+/// 打印新建规则的字段
+fn print_route_fields(state: &RouteState) {
+    println!("      {:<13} {}", "action:", state.action);
+    if !state.destinations.is_empty() {
+        println!(
+            "      {:<13} {}",
+            "destinations:",
+            state.destinations.join(", ")
+        );
+    }
+    println!("      {:<13} {}", "enabled:", state.enabled);
+}
+
+/// 打印字段变更
+fn print_field_change(field: &str, before: &str, after: &str) {
+    println!(
+        "      {}: {} {} {}",
+        field,
+        before.red(),
+        "→".dimmed(),
+        after.green()
+    );
+}
@@ -0,0 +1,29 @@
+use anyhow::{Context, Result, anyhow};
+use std::process::Command;
+
+/// 使用系统默认浏览器打开链接，只接受 http/https 地址
+///
+/// 链接可能来自不可信的邮件，因此不经过 shell 启动浏览器，避免其中的字符被当作命令执行。
+pub fn open_url(url: &str) -> Result<()> {
+    let parsed = reqwest::Url::parse(url).with_context(|| format!("无效的链接: {}", url))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(anyhow!("不支持的链接协议: {}", parsed.scheme()));
+    }
+    let url = parsed.as_str();
+
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+
+    let status = command.arg(url).status().context("无法启动浏览器")?;
+    if !status.success() {
+        anyhow::bail!("打开浏览器失败: {}", status);
+    }
+    Ok(())
+}
@@ -1,11 +1,8 @@
 use once_cell::sync::Lazy;
+use rust_embed::RustEmbed;
 use serde_json::Value;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::Read;
-use std::path::Path;
 use std::sync::RwLock;
-use rust_embed::RustEmbed;
 
 #[derive(RustEmbed)]
 #[folder = "locales/"]
@@ -84,7 +81,7 @@ fn load_translations(locale: SupportedLocale) -> Result<(), String> {
             std::str::from_utf8(file.data.as_ref())
                 .map_err(|e| format!("Invalid UTF-8 in locale file {}: {}", file_name, e))?
                 .to_string()
-        },
+        }
         None => return Err(format!("Locale file {} not found", file_name)),
     };
 
@@ -148,10 +145,10 @@ pub fn init_locale() {
     set_locale(locale);
 
     // 同时加载英文作为后备
-    if locale != SupportedLocale::EnUS {
-        if let Err(e) = load_translations(SupportedLocale::EnUS) {
-            eprintln!("Warning: Failed to load English translations: {}", e);
-        }
+    if locale != SupportedLocale::EnUS
+        && let Err(e) = load_translations(SupportedLocale::EnUS)
+    {
+        eprintln!("Warning: Failed to load English translations: {}", e);
     }
 }
 
@@ -161,19 +158,18 @@ fn get_translation(key: &str) -> Option<String> {
     let current = *CURRENT_LOCALE.read().unwrap();
 
     // 尝试从当前语言获取
-    if let Some(map) = translations.get(current.as_str()) {
-        if let Some(value) = map.get(key) {
-            return Some(value.clone());
-        }
+    if let Some(map) = translations.get(current.as_str())
+        && let Some(value) = map.get(key)
+    {
+        return Some(value.clone());
     }
 
     // 如果当前语言没有这个键，尝试从英文获取
-    if current != SupportedLocale::EnUS {
-        if let Some(map) = translations.get(SupportedLocale::EnUS.as_str()) {
-            if let Some(value) = map.get(key) {
-                return Some(value.clone());
-            }
-        }
+    if current != SupportedLocale::EnUS
+        && let Some(map) = translations.get(SupportedLocale::EnUS.as_str())
+        && let Some(value) = map.get(key)
+    {
+        return Some(value.clone());
     }
 
     None
@@ -208,6 +204,21 @@ pub fn get_current_locale_name() -> &'static str {
 }
 
 /// 列出所有支持的语言
+#[allow(dead_code)]
 pub fn list_supported_locales() -> Vec<(&'static str, &'static str)> {
     vec![("en", "English"), ("zh", "简体中文")]
 }
+
+/// 在指定语言下运行测试代码，结束后恢复英文
+///
+/// 语言是全局状态，使用互斥锁避免并行的测试相互影响。
+#[cfg(test)]
+pub fn with_locale<R>(locale: SupportedLocale, f: impl FnOnce() -> R) -> R {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    set_locale(locale);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    set_locale(SupportedLocale::EnUS);
+    result.unwrap_or_else(|e| std::panic::resume_unwind(e))
+}
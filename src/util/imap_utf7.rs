@@ -0,0 +1,124 @@
+//! IMAP 文件夹名称的修改版 UTF-7 编码（RFC 3501 5.1.3）
+//!
+//! 服务器在 LIST 中返回、在 SELECT 中接受的都是编码后的名称，例如“垃圾邮件”为 `&V4NXPpCuTvY-`。
+
+use base64::Engine;
+use base64::alphabet::IMAP_MUTF7;
+use base64::engine::general_purpose::{GeneralPurpose, NO_PAD};
+
+const ENGINE: GeneralPurpose = GeneralPurpose::new(&IMAP_MUTF7, NO_PAD);
+
+/// 将文件夹名称编码为修改版 UTF-7
+pub fn encode_mailbox(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut pending: Vec<u16> = Vec::new();
+
+    for c in name.chars() {
+        if (' '..='~').contains(&c) {
+            flush(&mut out, &mut pending);
+            if c == '&' {
+                out.push_str("&-");
+            } else {
+                out.push(c);
+            }
+        } else {
+            let mut units = [0u16; 2];
+            pending.extend_from_slice(c.encode_utf16(&mut units));
+        }
+    }
+    flush(&mut out, &mut pending);
+    out
+}
+
+/// 输出尚未编码的 UTF-16 字符
+fn flush(out: &mut String, pending: &mut Vec<u16>) {
+    if pending.is_empty() {
+        return;
+    }
+    let bytes: Vec<u8> = pending.iter().flat_map(|unit| unit.to_be_bytes()).collect();
+    out.push('&');
+    out.push_str(&ENGINE.encode(bytes));
+    out.push('-');
+    pending.clear();
+}
+
+/// 解码修改版 UTF-7 的文件夹名称，编码无效时返回 None
+///
+/// 支持 UTF8=ACCEPT 的服务器可能直接返回 UTF-8 名称，其中的非 ASCII 字符原样保留。
+pub fn decode_mailbox(name: &str) -> Option<String> {
+    let mut out = String::with_capacity(name.len());
+    let mut rest = name;
+
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let encoded = &rest[start + 1..];
+        let end = encoded.find('-')?;
+        if end == 0 {
+            out.push('&');
+        } else {
+            let bytes = ENGINE.decode(&encoded[..end]).ok()?;
+            if bytes.len() % 2 != 0 {
+                return None;
+            }
+            let units: Vec<u16> = bytes
+                .chunks(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            out.push_str(&String::from_utf16(&units).ok()?);
+        }
+        rest = &encoded[end + 1..];
+    }
+
+    out.push_str(rest);
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_names_are_unchanged() {
+        assert_eq!(encode_mailbox("INBOX"), "INBOX");
+        assert_eq!(encode_mailbox("[Gmail]/Spam"), "[Gmail]/Spam");
+        assert_eq!(
+            decode_mailbox("[Gmail]/Spam").as_deref(),
+            Some("[Gmail]/Spam")
+        );
+    }
+
+    #[test]
+    fn ampersand_is_escaped() {
+        assert_eq!(encode_mailbox("R&D"), "R&-D");
+        assert_eq!(decode_mailbox("R&-D").as_deref(), Some("R&D"));
+    }
+
+    #[test]
+    fn rfc3501_example() {
+        let name = "~peter/mail/台北/日本語";
+        let encoded = "~peter/mail/&U,BTFw-/&ZeVnLIqe-";
+        assert_eq!(encode_mailbox(name), encoded);
+        assert_eq!(decode_mailbox(encoded).as_deref(), Some(name));
+    }
+
+    #[test]
+    fn round_trips_mixed_names() {
+        for name in [
+            "垃圾邮件",
+            "Entwürfe",
+            "Gesendete Objekte",
+            "📁 Archiv & Co",
+            "a\u{7f}b",
+        ] {
+            assert_eq!(decode_mailbox(&encode_mailbox(name)).as_deref(), Some(name));
+        }
+        assert_eq!(encode_mailbox("垃圾邮件"), "&V4NXPpCuTvY-");
+    }
+
+    #[test]
+    fn rejects_malformed_names() {
+        assert_eq!(decode_mailbox("&V4NX"), None);
+        assert_eq!(decode_mailbox("&V4N-"), None);
+        assert_eq!(decode_mailbox("&*-"), None);
+    }
+}
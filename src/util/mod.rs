@@ -1,2 +1,4 @@
+pub mod browser;
 pub mod clipboard;
 pub mod i18n;
+pub mod imap_utf7;
@@ -0,0 +1,676 @@
+//! 测试用的最小IMAP服务器
+//!
+//! 只实现 cfmail 用到的命令，监听本机随机端口，支持 IDLE、XOAUTH2 和可选的 STARTTLS。
+//! 默认只有 INBOX，可以用 [`FakeImap::add_folder`] 添加其他文件夹。
+//! 邮件和命令记录保存在共享状态中，测试可以在监听过程中投递新邮件、断开连接，
+//! 并在结束后检查服务器收到的命令。
+
+use base64::Engine;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// IDLE 期间检查新邮件的间隔
+const IDLE_TICK: Duration = Duration::from_millis(50);
+
+/// 客户端进入 IDLE 多久后投递 `deliver_during_idle` 的邮件
+const IDLE_DELIVERY_DELAY: Duration = Duration::from_millis(300);
+
+const INBOX: &str = "INBOX";
+
+/// 服务器支持的扩展
+#[derive(Clone, Copy)]
+struct Features {
+    idle: bool,
+    starttls: bool,
+}
+
+/// 服务器上的一封邮件
+struct StoredMessage {
+    uid: u32,
+    /// 所在文件夹（服务器上的名称）
+    folder: String,
+    raw: Vec<u8>,
+    seen: bool,
+    deleted: bool,
+}
+
+#[derive(Default)]
+struct State {
+    /// LIST 返回的文件夹（修改版 UTF-7 编码）
+    folders: Vec<String>,
+    messages: Vec<StoredMessage>,
+    next_uid: u32,
+    /// 应答指定次数的搜索后才投递到指定文件夹的邮件
+    scheduled: Vec<(usize, String, Vec<u8>)>,
+    /// 已处理的 UID SEARCH 次数
+    searches: usize,
+    /// 接下来需要直接断开连接的 UID SEARCH 次数
+    drop_searches: usize,
+    /// 客户端进入 IDLE 后才投递的邮件
+    idle_deliveries: Vec<Vec<u8>>,
+    /// 是否拒绝添加 \\Seen 标志的 STORE
+    reject_seen: bool,
+    /// XOAUTH2 登录接受的访问令牌
+    access_token: Option<String>,
+    /// 收到的命令（不含标签）
+    log: Vec<String>,
+}
+
+impl State {
+    fn store(&mut self, folder: &str, raw: Vec<u8>) -> u32 {
+        let uid = self.next_uid;
+        self.next_uid += 1;
+        self.messages.push(StoredMessage {
+            uid,
+            folder: folder.to_string(),
+            raw,
+            seen: false,
+            deleted: false,
+        });
+        uid
+    }
+
+    /// 文件夹中的邮件，顺序即序号
+    fn in_folder<'a>(&'a self, folder: &'a str) -> impl Iterator<Item = &'a StoredMessage> {
+        self.messages.iter().filter(move |m| m.folder == folder)
+    }
+
+    /// 服务器上是否有该文件夹，INBOX 不区分大小写
+    fn find_folder(&self, name: &str) -> Option<String> {
+        self.folders
+            .iter()
+            .find(|folder| {
+                *folder == name
+                    || (folder.eq_ignore_ascii_case("INBOX") && name.eq_ignore_ascii_case("INBOX"))
+            })
+            .cloned()
+    }
+
+    /// 文件夹中未删除的邮件数量，即 EXISTS 的值
+    fn exists(&self, folder: &str) -> usize {
+        self.in_folder(folder).filter(|m| !m.deleted).count()
+    }
+
+    /// 投递已到时间的邮件
+    fn deliver_scheduled(&mut self) {
+        let searches = self.searches;
+        let (due, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.scheduled)
+            .into_iter()
+            .partition(|(after, _, _)| *after <= searches);
+        self.scheduled = pending;
+        for (_, folder, raw) in due {
+            self.store(&folder, raw);
+        }
+    }
+}
+
+/// 运行在后台线程中的IMAP服务器
+pub struct FakeImap {
+    port: u16,
+    features: Features,
+    state: Arc<Mutex<State>>,
+}
+
+impl FakeImap {
+    /// 在本机随机端口启动支持 IDLE 的明文服务器
+    pub fn start() -> Self {
+        Self::launch(Features {
+            idle: true,
+            starttls: false,
+        })
+    }
+
+    /// 启动不支持 IDLE 的服务器，客户端只能轮询
+    pub fn start_without_idle() -> Self {
+        Self::launch(Features {
+            idle: false,
+            starttls: false,
+        })
+    }
+
+    /// 启动需要先通过 STARTTLS 升级连接的服务器，证书见 [`tls_cert_path`]
+    pub fn start_starttls() -> Self {
+        Self::launch(Features {
+            idle: true,
+            starttls: true,
+        })
+    }
+
+    fn launch(features: Features) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let state = Arc::new(Mutex::new(State {
+            folders: vec![INBOX.to_string()],
+            next_uid: 1,
+            ..State::default()
+        }));
+
+        let shared = Arc::clone(&state);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = Arc::clone(&shared);
+                thread::spawn(move || handle_connection(stream, state, features));
+            }
+        });
+
+        Self {
+            port,
+            features,
+            state,
+        }
+    }
+
+    /// 指向该服务器的 `[smtp]` 配置
+    pub fn smtp_config(&self) -> String {
+        let security = if self.features.starttls {
+            format!(
+                "security = \"starttls\"\nca_cert = '{}'",
+                tls_cert_path().display()
+            )
+        } else {
+            "security = \"none\"".to_string()
+        };
+
+        format!(
+            r#"
+[smtp]
+username = "user"
+password = "pass"
+imap_server = "127.0.0.1"
+imap_port = {}
+{}
+smtp_server = "127.0.0.1"
+smtp_port = 587
+"#,
+            self.port, security
+        )
+    }
+
+    /// 使用 XOAUTH2 登录的 `[smtp]` 配置，刷新令牌时请求 `token_url`
+    pub fn oauth_config(&self, token_url: &str) -> String {
+        format!(
+            r#"
+[smtp]
+username = "user"
+auth_type = "xoauth2"
+imap_server = "127.0.0.1"
+imap_port = {}
+security = "none"
+smtp_server = "127.0.0.1"
+smtp_port = 587
+
+[smtp.oauth2]
+client_id = "client"
+token_url = "{}"
+scope = "imap"
+"#,
+            self.port, token_url
+        )
+    }
+
+    /// XOAUTH2 登录时接受的访问令牌
+    pub fn accept_access_token(&self, token: &str) {
+        self.state.lock().unwrap().access_token = Some(token.to_string());
+    }
+
+    /// 添加文件夹，`name` 为服务器上的名称（修改版 UTF-7 编码）
+    pub fn add_folder(&self, name: &str) {
+        self.state.lock().unwrap().folders.push(name.to_string());
+    }
+
+    /// 立即在收件箱中放入一封邮件（监听开始前已存在），返回 UID
+    pub fn add_message(&self, raw: impl Into<Vec<u8>>) -> u32 {
+        self.state.lock().unwrap().store(INBOX, raw.into())
+    }
+
+    /// 在服务器应答 `searches` 次搜索后向收件箱投递邮件，模拟监听期间送达的新邮件
+    pub fn deliver_after(&self, searches: usize, raw: impl Into<Vec<u8>>) {
+        self.deliver_to(INBOX, searches, raw);
+    }
+
+    /// 在服务器应答 `searches` 次搜索（包括其他文件夹中的）后向指定文件夹投递邮件
+    pub fn deliver_to(&self, folder: &str, searches: usize, raw: impl Into<Vec<u8>>) {
+        self.state
+            .lock()
+            .unwrap()
+            .scheduled
+            .push((searches, folder.to_string(), raw.into()));
+    }
+
+    /// 在客户端进入 IDLE 后向收件箱投递邮件，服务器随后推送 EXISTS 通知
+    pub fn deliver_during_idle(&self, raw: impl Into<Vec<u8>>) {
+        self.state.lock().unwrap().idle_deliveries.push(raw.into());
+    }
+
+    /// 接下来的 `count` 次搜索直接断开连接，模拟网络中断
+    pub fn drop_searches(&self, count: usize) {
+        self.state.lock().unwrap().drop_searches = count;
+    }
+
+    /// 拒绝之后所有添加 \\Seen 标志的 STORE 命令
+    pub fn reject_seen(&self) {
+        self.state.lock().unwrap().reject_seen = true;
+    }
+
+    /// 邮件是否已标记为已读，邮件不存在时返回 None
+    pub fn is_seen(&self, uid: u32) -> Option<bool> {
+        let state = self.state.lock().unwrap();
+        state
+            .messages
+            .iter()
+            .find(|message| message.uid == uid)
+            .map(|message| message.seen)
+    }
+
+    /// 邮件是否仍在服务器上（未被移走或清除）
+    pub fn contains(&self, uid: u32) -> bool {
+        self.is_seen(uid).is_some()
+    }
+
+    /// 服务器收到的所有命令
+    pub fn commands(&self) -> Vec<String> {
+        self.state.lock().unwrap().log.clone()
+    }
+}
+
+/// 测试证书，签发给 127.0.0.1，同时作为客户端信任的 CA
+pub fn tls_cert_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/common/tls/cert.pem")
+}
+
+fn tls_acceptor() -> native_tls::TlsAcceptor {
+    let dir = tls_cert_path().parent().unwrap().to_path_buf();
+    let cert = std::fs::read(dir.join("cert.pem")).unwrap();
+    let key = std::fs::read(dir.join("key.pem")).unwrap();
+    let identity = native_tls::Identity::from_pkcs8(&cert, &key).unwrap();
+    native_tls::TlsAcceptor::new(identity).unwrap()
+}
+
+/// 明文或TLS连接
+trait Stream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Stream for T {}
+
+/// 单个连接的状态
+struct Connection {
+    reader: BufReader<Box<dyn Stream>>,
+    /// 底层套接字，用于设置读取超时和断开连接
+    socket: TcpStream,
+    state: Arc<Mutex<State>>,
+    features: Features,
+    tls: bool,
+    /// 当前选中的文件夹
+    selected: String,
+    /// 最近一次告知客户端的邮件数量
+    exists: usize,
+}
+
+impl Connection {
+    fn write(&mut self, bytes: &[u8]) -> bool {
+        let stream = self.reader.get_mut();
+        stream.write_all(bytes).is_ok() && stream.flush().is_ok()
+    }
+
+    /// 升级为TLS连接，客户端在收到 OK 之前不会发送数据，缓冲区为空
+    fn start_tls(&mut self) -> bool {
+        let Ok(socket) = self.socket.try_clone() else {
+            return false;
+        };
+        match tls_acceptor().accept(socket) {
+            Ok(stream) => {
+                self.reader = BufReader::new(Box::new(stream));
+                self.tls = true;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// 处理 IDLE：有新邮件时推送 EXISTS，直到客户端发送 DONE
+    fn idle(&mut self, tag: &str) -> bool {
+        if !self.write(b"+ idling\r\n") {
+            return false;
+        }
+
+        let started = Instant::now();
+        let _ = self.socket.set_read_timeout(Some(IDLE_TICK));
+        let mut line = String::new();
+        let done = loop {
+            let exists = {
+                let mut state = self.state.lock().unwrap();
+                if started.elapsed() >= IDLE_DELIVERY_DELAY {
+                    for raw in std::mem::take(&mut state.idle_deliveries) {
+                        state.store(INBOX, raw);
+                    }
+                }
+                state.exists(&self.selected)
+            };
+            if exists != self.exists {
+                self.exists = exists;
+                if !self.write(format!("* {} EXISTS\r\n", exists).as_bytes()) {
+                    break false;
+                }
+            }
+
+            // 超时返回时已读取的部分保留在 line 中
+            match self.reader.read_line(&mut line) {
+                Ok(0) => break false,
+                Ok(_) if line.trim().eq_ignore_ascii_case("DONE") => break true,
+                Ok(_) => line.clear(),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(_) => break false,
+            }
+        };
+        let _ = self.socket.set_read_timeout(None);
+
+        done && self.write(format!("{} OK IDLE terminated\r\n", tag).as_bytes())
+    }
+
+    /// 处理 AUTHENTICATE XOAUTH2，记录解码后的初始响应
+    fn authenticate(&mut self, tag: &str) -> bool {
+        if !self.write(b"+ \r\n") {
+            return false;
+        }
+        let mut line = String::new();
+        if !matches!(self.reader.read_line(&mut line), Ok(n) if n > 0) {
+            return false;
+        }
+
+        let response = base64::engine::general_purpose::STANDARD
+            .decode(line.trim_end())
+            .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+            .unwrap_or_default();
+        let accepted = {
+            let mut state = self.state.lock().unwrap();
+            state.log.push(response.clone());
+            state.access_token.as_ref().is_some_and(|token| {
+                response == format!("user=user\x01auth=Bearer {}\x01\x01", token)
+            })
+        };
+
+        let reply = if accepted {
+            format!("{} OK authenticated\r\n", tag)
+        } else {
+            format!("{} NO invalid token\r\n", tag)
+        };
+        self.write(reply.as_bytes())
+    }
+}
+
+fn handle_connection(stream: TcpStream, state: Arc<Mutex<State>>, features: Features) {
+    let Ok(socket) = stream.try_clone() else {
+        return;
+    };
+    let mut connection = Connection {
+        reader: BufReader::new(Box::new(stream)),
+        socket,
+        state,
+        features,
+        tls: false,
+        selected: INBOX.to_string(),
+        exists: 0,
+    };
+
+    if !connection.write(b"* OK fake IMAP ready\r\n") {
+        return;
+    }
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match connection.reader.read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+
+        let line = line.trim_end();
+        let Some((tag, command)) = line.split_once(' ') else {
+            continue;
+        };
+
+        if command.eq_ignore_ascii_case("IDLE") && features.idle {
+            connection
+                .state
+                .lock()
+                .unwrap()
+                .log
+                .push(command.to_string());
+            if !connection.idle(tag) {
+                return;
+            }
+            continue;
+        }
+
+        if command.eq_ignore_ascii_case("AUTHENTICATE XOAUTH2") {
+            connection
+                .state
+                .lock()
+                .unwrap()
+                .log
+                .push(command.to_string());
+            if !connection.authenticate(tag) {
+                return;
+            }
+            continue;
+        }
+
+        let Some(response) = respond(&mut connection, tag, command) else {
+            // 模拟连接中断
+            let _ = connection.socket.shutdown(std::net::Shutdown::Both);
+            return;
+        };
+
+        if !connection.write(&response) {
+            return;
+        }
+        if command.eq_ignore_ascii_case("LOGOUT") {
+            return;
+        }
+        if command.eq_ignore_ascii_case("STARTTLS")
+            && features.starttls
+            && !connection.tls
+            && !connection.start_tls()
+        {
+            return;
+        }
+    }
+}
+
+/// 生成命令的应答，返回 None 时断开连接
+fn respond(connection: &mut Connection, tag: &str, command: &str) -> Option<Vec<u8>> {
+    let mut state = connection.state.lock().unwrap();
+    state.log.push(command.to_string());
+
+    let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+    let mut out = Vec::new();
+    let ok = |out: &mut Vec<u8>| out.extend_from_slice(format!("{} OK done\r\n", tag).as_bytes());
+
+    match name.to_ascii_uppercase().as_str() {
+        "CAPABILITY" => {
+            let mut capabilities = String::from("IMAP4rev1 UIDPLUS MOVE AUTH=XOAUTH2");
+            if connection.features.idle {
+                capabilities.push_str(" IDLE");
+            }
+            if connection.features.starttls && !connection.tls {
+                capabilities.push_str(" STARTTLS LOGINDISABLED");
+            }
+            out.extend_from_slice(format!("* CAPABILITY {}\r\n", capabilities).as_bytes());
+            ok(&mut out);
+        }
+        "STARTTLS" if connection.features.starttls && !connection.tls => {
+            out.extend_from_slice(format!("{} OK begin TLS negotiation\r\n", tag).as_bytes());
+        }
+        "LOGIN" => {
+            if connection.features.starttls && !connection.tls {
+                out.extend_from_slice(format!("{} NO STARTTLS required\r\n", tag).as_bytes());
+            } else if args == "\"user\" \"pass\"" {
+                ok(&mut out);
+            } else {
+                out.extend_from_slice(format!("{} NO invalid credentials\r\n", tag).as_bytes());
+            }
+        }
+        "LIST" => {
+            for folder in &state.folders {
+                out.extend_from_slice(
+                    format!("* LIST (\\HasNoChildren) \"/\" \"{}\"\r\n", folder).as_bytes(),
+                );
+            }
+            ok(&mut out);
+        }
+        "SELECT" | "EXAMINE" => {
+            let Some(folder) = state.find_folder(args.trim_matches('"')) else {
+                out.extend_from_slice(format!("{} NO no such mailbox\r\n", tag).as_bytes());
+                return Some(out);
+            };
+            let exists = state.exists(&folder);
+            connection.selected = folder;
+            connection.exists = exists;
+            out.extend_from_slice(
+                format!(
+                    "* {} EXISTS\r\n* OK [UIDVALIDITY 1] UIDs valid\r\n* OK [UIDNEXT {}] next UID\r\n{} OK [READ-WRITE] selected\r\n",
+                    exists, state.next_uid, tag
+                )
+                .as_bytes(),
+            );
+        }
+        "UID" => {
+            let (sub, rest) = args.split_once(' ').unwrap_or((args, ""));
+            match sub.to_ascii_uppercase().as_str() {
+                "SEARCH" => {
+                    state.searches += 1;
+                    if state.drop_searches > 0 {
+                        state.drop_searches -= 1;
+                        state.deliver_scheduled();
+                        return None;
+                    }
+
+                    let from = search_from(rest);
+                    let uids: Vec<String> = state
+                        .in_folder(&connection.selected)
+                        .filter(|m| !m.seen && !m.deleted)
+                        .filter(|m| match &from {
+                            Some(from) => header(&m.raw, "From")
+                                .to_lowercase()
+                                .contains(&from.to_lowercase()),
+                            None => true,
+                        })
+                        .map(|m| m.uid.to_string())
+                        .collect();
+                    let line = format!("* SEARCH {}", uids.join(" "));
+                    out.extend_from_slice(format!("{}\r\n", line.trim_end()).as_bytes());
+                    ok(&mut out);
+
+                    // 本次搜索之后送达的邮件
+                    state.deliver_scheduled();
+                }
+                "FETCH" => {
+                    let uid: u32 = rest.split(' ').next()?.parse().ok()?;
+                    if let Some((index, message)) = state
+                        .in_folder(&connection.selected)
+                        .enumerate()
+                        .find(|(_, m)| m.uid == uid)
+                    {
+                        out.extend_from_slice(
+                            format!(
+                                "* {} FETCH (UID {} BODY[] {{{}}}\r\n",
+                                index + 1,
+                                uid,
+                                message.raw.len()
+                            )
+                            .as_bytes(),
+                        );
+                        out.extend_from_slice(&message.raw);
+                        out.extend_from_slice(b")\r\n");
+                    }
+                    ok(&mut out);
+                }
+                "STORE" if state.reject_seen && rest.contains("\\Seen") => {
+                    out.extend_from_slice(format!("{} NO cannot set flags\r\n", tag).as_bytes());
+                }
+                "STORE" => {
+                    let uid: u32 = rest.split(' ').next()?.parse().ok()?;
+                    if let Some(message) = state.messages.iter_mut().find(|m| m.uid == uid) {
+                        if rest.contains("\\Seen") {
+                            message.seen = true;
+                        }
+                        if rest.contains("\\Deleted") {
+                            message.deleted = true;
+                        }
+                    }
+                    ok(&mut out);
+                }
+                "MOVE" => {
+                    let uid: u32 = rest.split(' ').next()?.parse().ok()?;
+                    state.messages.retain(|m| m.uid != uid);
+                    ok(&mut out);
+                }
+                "EXPUNGE" => {
+                    let selected = &connection.selected;
+                    state
+                        .messages
+                        .retain(|m| !(m.deleted && m.folder == *selected));
+                    ok(&mut out);
+                }
+                _ => out.extend_from_slice(format!("{} BAD unsupported\r\n", tag).as_bytes()),
+            }
+        }
+        "EXPUNGE" => {
+            let selected = &connection.selected;
+            state
+                .messages
+                .retain(|m| !(m.deleted && m.folder == *selected));
+            ok(&mut out);
+        }
+        "NOOP" => ok(&mut out),
+        "LOGOUT" => {
+            out.extend_from_slice(b"* BYE logging out\r\n");
+            ok(&mut out);
+        }
+        _ => out.extend_from_slice(format!("{} BAD unsupported\r\n", tag).as_bytes()),
+    }
+
+    Some(out)
+}
+
+/// 解析搜索条件中的 FROM "..."
+fn search_from(criteria: &str) -> Option<String> {
+    let start = criteria.find("FROM \"")? + "FROM \"".len();
+    let end = criteria[start..].find('"')? + start;
+    Some(criteria[start..end].to_string())
+}
+
+/// 读取邮件头（不处理折行，测试邮件足够用）
+fn header(raw: &[u8], name: &str) -> String {
+    let text = String::from_utf8_lossy(raw);
+    let prefix = format!("{}:", name.to_lowercase());
+    text.lines()
+        .take_while(|line| !line.is_empty())
+        .find(|line| line.to_lowercase().starts_with(&prefix))
+        .map(|line| line[prefix.len()..].trim().to_string())
+        .unwrap_or_default()
+}
+
+/// 构造纯文本邮件，Date 为当前时间
+pub fn text_message(from: &str, subject: &str, body: &str) -> String {
+    message(from, subject, "text/plain", body)
+}
+
+/// 构造只有HTML正文的邮件，Date 为当前时间
+pub fn html_message(from: &str, subject: &str, html: &str) -> String {
+    message(from, subject, "text/html", html)
+}
+
+fn message(from: &str, subject: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "From: {}\r\nTo: me@example.net\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\nContent-Type: {}; charset=utf-8\r\n\r\n{}\r\n",
+        from,
+        subject,
+        chrono::Utc::now().to_rfc2822(),
+        content_type,
+        body.replace('\n', "\r\n")
+    )
+}
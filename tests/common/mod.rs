@@ -0,0 +1,94 @@
+//! 集成测试共用的辅助函数
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub mod imap;
+pub mod oauth;
+
+/// 测试使用的最小配置，`[smtp]` 段由各测试补充
+pub const BASE_CONFIG: &str = r#"
+[cloudflare]
+api_key = "test"
+api_token = ""
+zone_id = "test"
+email = "test@example.com"
+
+[email]
+domain = "example.com"
+target_email = "me@example.net"
+
+[alias]
+prefix_mode = "random"
+custom_prefixes = []
+random_length = 8
+random_charset = "alphabetic"
+"#;
+
+/// 默认的 `[smtp]` 配置
+pub const DEFAULT_SMTP: &str = r#"
+[smtp]
+username = "user"
+password = "pass"
+imap_server = "127.0.0.1"
+imap_port = 993
+smtp_server = "127.0.0.1"
+smtp_port = 587
+"#;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// 带有配置文件的临时主目录，离开作用域时删除
+pub struct TempHome(PathBuf);
+
+impl TempHome {
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempHome {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// 创建带有配置文件的临时主目录
+pub fn temp_home(extra_config: &str) -> TempHome {
+    let home = std::env::temp_dir().join(format!(
+        "cfmail-test-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    let config_dir = home.join(".config").join("cfmail");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.toml"),
+        format!("{}{}", BASE_CONFIG, extra_config),
+    )
+    .unwrap();
+    TempHome(home)
+}
+
+/// 在指定主目录下运行 cfmail（英文界面、无颜色）
+pub fn run_cfmail(home: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cfmail"))
+        .arg("--locale")
+        .arg("en")
+        .args(args)
+        .env("HOME", home)
+        .env("NO_COLOR", "1")
+        .env("LANG", "C")
+        .env_remove("LC_ALL")
+        .output()
+        .expect("failed to run cfmail")
+}
+
+/// 测试用邮件样本目录
+pub fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use common::{DEFAULT_SMTP, fixtures_dir, run_cfmail, temp_home};
+
+/// 对单个样本运行 extract，返回标准输出
+fn extract(fixture: &str, extra_args: &[&str]) -> String {
+    let home = temp_home(DEFAULT_SMTP);
+    let path = fixtures_dir().join(fixture);
+    let mut args = vec!["extract", path.to_str().unwrap()];
+    args.extend_from_slice(extra_args);
+
+    let output = run_cfmail(home.path(), &args);
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    assert!(
+        output.status.success(),
+        "extract {} failed:\n{}\n{}",
+        fixture,
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    stdout
+}
+
+fn assert_chosen(fixture: &str, extra_args: &[&str], code: &str) {
+    let stdout = extract(fixture, extra_args);
+    assert!(
+        stdout.contains(&format!("Chosen code: {} ", code)),
+        "expected {} in {}:\n{}",
+        code,
+        fixture,
+        stdout
+    );
+}
+
+#[test]
+fn plain_text_code() {
+    assert_chosen("en_plain_code_is.eml", &[], "482913");
+}
+
+#[test]
+fn html_only_styled_code() {
+    assert_chosen("en_html_only_styled.eml", &[], "739201");
+}
+
+#[test]
+fn multipart_alternative() {
+    assert_chosen("en_multipart_alternative.eml", &[], "615204");
+}
+
+#[test]
+fn ignores_order_number_and_phone() {
+    assert_chosen("en_order_noise.eml", &[], "308417");
+}
+
+#[test]
+fn quoted_printable_html() {
+    assert_chosen("en_quoted_printable.eml", &[], "924671");
+}
+
+#[test]
+fn alphanumeric_code() {
+    assert_chosen(
+        "en_alphanumeric.eml",
+        &["--code-type", "alphanumeric"],
+        "K7P2QX",
+    );
+}
+
+#[test]
+fn chinese_plain_base64() {
+    assert_chosen("zh_plain_base64.eml", &[], "583920");
+}
+
+#[test]
+fn chinese_html_quoted_printable() {
+    assert_chosen("zh_html_qp.eml", &[], "271845");
+}
+
+#[test]
+fn newsletter_without_code() {
+    let stdout = extract("en_newsletter_no_code.eml", &[]);
+    assert!(stdout.contains("No verification code found"), "{}", stdout);
+}
+
+#[test]
+fn custom_rule_from_config() {
+    let home = temp_home(&format!(
+        "{}\n[[extract.rules]]\nsender = '@vault\\.test$'\npattern = 'Sign-in code:\\s*([A-Z0-9]{{6}})'\n",
+        DEFAULT_SMTP
+    ));
+    let path = fixtures_dir().join("en_alphanumeric.eml");
+    let output = run_cfmail(home.path(), &["extract", path.to_str().unwrap()]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Chosen code: K7P2QX "), "{}", stdout);
+    assert!(stdout.contains("rule-1"), "{}", stdout);
+}
+
+#[test]
+fn directory_summary() {
+    let home = temp_home(DEFAULT_SMTP);
+    let output = run_cfmail(home.path(), &["extract", fixtures_dir().to_str().unwrap()]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    // 字母数字验证码需要 --code-type，没有验证码的邮件不计入
+    assert!(stdout.contains("Found codes in 7/9 emails"), "{}", stdout);
+}
+
+#[test]
+fn length_applies_to_all_rules() {
+    let stdout = extract("en_plain_code_is.eml", &["--length", "4"]);
+    assert!(stdout.contains("No verification code found"), "{}", stdout);
+}
+
+#[test]
+fn code_type_any_accepts_alphanumeric() {
+    assert_chosen("en_alphanumeric.eml", &["--code-type", "any"], "K7P2QX");
+}
+
+#[test]
+fn explicit_code_type_applies_to_custom_rules() {
+    let home = temp_home(&format!(
+        "{}\n[[extract.rules]]\npattern = 'Sign-in code:\\s*([A-Z0-9]{{6}})'\n",
+        DEFAULT_SMTP
+    ));
+    let path = fixtures_dir().join("en_alphanumeric.eml");
+    let output = run_cfmail(
+        home.path(),
+        &["extract", path.to_str().unwrap(), "--code-type", "numeric"],
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("No verification code found"), "{}", stdout);
+}
+
+#[test]
+fn works_without_config_file() {
+    let home = temp_home("");
+    std::fs::remove_file(home.path().join(".config/cfmail/config.toml")).unwrap();
+    let path = fixtures_dir().join("en_plain_code_is.eml");
+
+    let output = run_cfmail(home.path(), &["extract", path.to_str().unwrap()]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("built-in rules only"), "{}", stdout);
+    assert!(stdout.contains("Chosen code: 482913 "), "{}", stdout);
+}
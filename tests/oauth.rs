@@ -0,0 +1,153 @@
+mod common;
+
+use common::imap::{FakeImap, text_message};
+use common::oauth::FakeTokenEndpoint;
+use common::{TempHome, run_cfmail, temp_home};
+use std::path::PathBuf;
+use std::process::Output;
+use std::time::{Duration, Instant};
+
+fn tokens_path(home: &TempHome) -> PathBuf {
+    home.path().join(".config/cfmail/tokens.toml")
+}
+
+/// 保存 user 账户的令牌，`expires_in` 为访问令牌剩余的有效期（秒）
+fn store_token(home: &TempHome, access_token: &str, expires_in: i64) {
+    let expires_at = chrono::Utc::now().timestamp() + expires_in;
+    std::fs::write(
+        tokens_path(home),
+        format!(
+            "[[token]]\naccount = \"user\"\nrefresh_token = \"refresh-1\"\naccess_token = \"{}\"\nexpires_at = {}\n",
+            access_token, expires_at
+        ),
+    )
+    .unwrap();
+}
+
+fn watch_code(home: &TempHome) -> Output {
+    run_cfmail(
+        home.path(),
+        &["watch-code", "--poll-interval", "1", "--timeout", "15"],
+    )
+}
+
+fn assert_code(output: &Output, code: &str) {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success() && stdout.contains(&format!("  {}", code)),
+        "expected code {}:\n{}\n{}",
+        code,
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn refreshes_expired_token_before_login() {
+    let server = FakeImap::start();
+    let endpoint = FakeTokenEndpoint::start("fresh-token");
+    server.accept_access_token("fresh-token");
+    server.deliver_after(
+        1,
+        text_message(
+            "noreply@example.com",
+            "Code",
+            "Your verification code is 482913.",
+        ),
+    );
+
+    let home = temp_home(&server.oauth_config(&endpoint.url()));
+    store_token(&home, "stale-token", -60);
+
+    assert_code(&watch_code(&home), "482913");
+
+    let requests = endpoint.requests();
+    assert_eq!(requests.len(), 1, "{:?}", requests);
+    assert!(requests[0].contains("grant_type=refresh_token"));
+    assert!(requests[0].contains("refresh_token=refresh-1"));
+    assert!(
+        server
+            .commands()
+            .contains(&"user=user\x01auth=Bearer fresh-token\x01\x01".to_string()),
+        "{:?}",
+        server.commands()
+    );
+
+    // 新的访问令牌写回文件，未返回新刷新令牌时保留原来的
+    let saved = std::fs::read_to_string(tokens_path(&home)).unwrap();
+    assert!(
+        saved.contains("access_token = \"fresh-token\""),
+        "{}",
+        saved
+    );
+    assert!(saved.contains("refresh_token = \"refresh-1\""), "{}", saved);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(tokens_path(&home))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[test]
+fn valid_token_is_used_without_refresh() {
+    let server = FakeImap::start();
+    let endpoint = FakeTokenEndpoint::start("fresh-token");
+    server.accept_access_token("cached-token");
+    server.deliver_after(
+        1,
+        text_message(
+            "noreply@example.com",
+            "Code",
+            "Your verification code is 271845.",
+        ),
+    );
+
+    let home = temp_home(&server.oauth_config(&endpoint.url()));
+    store_token(&home, "cached-token", 3600);
+
+    assert_code(&watch_code(&home), "271845");
+    assert!(endpoint.requests().is_empty());
+}
+
+#[test]
+fn missing_token_fails_without_retrying() {
+    let server = FakeImap::start();
+    let endpoint = FakeTokenEndpoint::start("fresh-token");
+    let home = temp_home(&server.oauth_config(&endpoint.url()));
+
+    let started = Instant::now();
+    let output = watch_code(&home);
+
+    assert!(!output.status.success());
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("cfmail auth login"),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+#[test]
+fn rejected_token_fails_without_retrying() {
+    let server = FakeImap::start();
+    let endpoint = FakeTokenEndpoint::start("fresh-token");
+    server.accept_access_token("other-token");
+    let home = temp_home(&server.oauth_config(&endpoint.url()));
+    store_token(&home, "stale-token", -60);
+
+    let started = Instant::now();
+    let output = watch_code(&home);
+
+    assert!(!output.status.success());
+    assert!(started.elapsed() < Duration::from_secs(10));
+    let logins = server
+        .commands()
+        .iter()
+        .filter(|command| command.starts_with("AUTHENTICATE"))
+        .count();
+    assert_eq!(logins, 1);
+}
@@ -0,0 +1,549 @@
+mod common;
+
+use common::imap::{FakeImap, html_message, text_message};
+use common::{fixtures_dir, run_cfmail, temp_home};
+use std::process::Output;
+use std::time::{Duration, Instant};
+
+/// 对测试服务器运行 watch-code
+fn watch_code(server: &FakeImap, extra_args: &[&str]) -> Output {
+    let home = temp_home(&server.smtp_config());
+    let mut args = vec!["watch-code", "--poll-interval", "1", "--timeout", "15"];
+    args.extend_from_slice(extra_args);
+    run_cfmail(home.path(), &args)
+}
+
+/// 断言找到了指定的验证码
+fn assert_code(output: &Output, code: &str) {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success() && stdout.contains(&format!("  {}", code)),
+        "expected code {}:\n{}\n{}",
+        code,
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+fn fixture(name: &str) -> Vec<u8> {
+    std::fs::read(fixtures_dir().join(name)).unwrap()
+}
+
+#[test]
+fn finds_code_delivered_during_watch() {
+    let server = FakeImap::start();
+    server.deliver_after(
+        1,
+        text_message(
+            "GitHub <noreply@github.com>",
+            "Your code",
+            "Your verification code is 482913.",
+        ),
+    );
+
+    assert_code(&watch_code(&server, &[]), "482913");
+}
+
+#[test]
+fn ignores_existing_mail_without_since() {
+    let server = FakeImap::start();
+    server.add_message(text_message(
+        "old@example.com",
+        "Old code",
+        "Your verification code is 111111.",
+    ));
+    server.deliver_after(
+        1,
+        text_message(
+            "new@example.com",
+            "New code",
+            "Your verification code is 222222.",
+        ),
+    );
+
+    assert_code(&watch_code(&server, &[]), "222222");
+}
+
+#[test]
+fn since_picks_newest_existing_message() {
+    let server = FakeImap::start();
+    server.add_message(text_message(
+        "a@example.com",
+        "First",
+        "Your verification code is 111111.",
+    ));
+    server.add_message(text_message(
+        "b@example.com",
+        "Second",
+        "Your verification code is 222222.",
+    ));
+
+    assert_code(&watch_code(&server, &["--since", "1h"]), "222222");
+}
+
+#[test]
+fn sender_filter_skips_other_senders() {
+    let server = FakeImap::start();
+    server.deliver_after(
+        1,
+        text_message(
+            "GitHub <noreply@github.com>",
+            "GitHub code",
+            "Your verification code is 482913.",
+        ),
+    );
+    // 更新的邮件来自其他发件人
+    server.deliver_after(
+        1,
+        text_message(
+            "Shop <noreply@shop.example>",
+            "Shop code",
+            "Your verification code is 739201.",
+        ),
+    );
+
+    let output = watch_code(&server, &["--from", "github.com"]);
+    assert_code(&output, "482913");
+    assert!(
+        server
+            .commands()
+            .iter()
+            .any(|command| command.contains("FROM \"github.com\""))
+    );
+}
+
+#[test]
+fn recipient_filter_skips_other_aliases() {
+    let server = FakeImap::start();
+    // 通过 Cloudflare 转发后 To 仍是目标邮箱，别名记录在 Delivered-To 中
+    server.deliver_after(
+        1,
+        format!(
+            "Delivered-To: shop+promo@example.com\r\n{}",
+            text_message(
+                "noreply@shop.example",
+                "Shop code",
+                "Your verification code is 482913.",
+            )
+        ),
+    );
+    // 更新的邮件发给了另一个别名
+    server.deliver_after(
+        1,
+        text_message(
+            "noreply@other.example",
+            "Other code",
+            "Your verification code is 739201.",
+        )
+        .replace("To: me@example.net", "To: other@example.com"),
+    );
+
+    let output = watch_code(&server, &["--to", "shop@example.com"]);
+    assert_code(&output, "482913");
+    assert_eq!(server.is_seen(1), Some(true));
+    assert_eq!(server.is_seen(2), Some(false));
+}
+
+#[test]
+fn recipient_filter_matches_forwarded_to() {
+    let server = FakeImap::start();
+    server.deliver_after(
+        1,
+        format!(
+            "X-Forwarded-To: Shop@Example.com\r\n{}",
+            text_message(
+                "noreply@shop.example",
+                "Shop code",
+                "Your verification code is 271845.",
+            )
+        ),
+    );
+
+    assert_code(
+        &watch_code(&server, &["--to", "shop@example.com"]),
+        "271845",
+    );
+}
+
+#[test]
+fn html_only_message() {
+    let server = FakeImap::start();
+    server.deliver_after(1, fixture("en_html_only_styled.eml"));
+
+    assert_code(&watch_code(&server, &[]), "739201");
+}
+
+#[test]
+fn html_only_message_built_inline() {
+    let server = FakeImap::start();
+    server.deliver_after(
+        1,
+        html_message(
+            "noreply@example.com",
+            "Confirm",
+            "<html><body><p>Your verification code is <b>615204</b></p></body></html>",
+        ),
+    );
+
+    assert_code(&watch_code(&server, &[]), "615204");
+}
+
+#[test]
+fn times_out_without_matching_mail() {
+    let server = FakeImap::start();
+    server.deliver_after(1, fixture("en_newsletter_no_code.eml"));
+
+    let home = temp_home(&server.smtp_config());
+    let output = run_cfmail(
+        home.path(),
+        &["watch-code", "--poll-interval", "1", "--timeout", "3"],
+    );
+    let combined = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(!output.status.success());
+    assert!(
+        combined.contains("No verification code found"),
+        "{}",
+        combined
+    );
+}
+
+#[test]
+fn reconnects_after_connection_drop() {
+    let server = FakeImap::start();
+    server.drop_searches(1);
+    server.deliver_after(
+        1,
+        text_message(
+            "noreply@example.com",
+            "Code",
+            "Your verification code is 308417.",
+        ),
+    );
+
+    let output = watch_code(&server, &[]);
+    assert_code(&output, "308417");
+
+    let logins = server
+        .commands()
+        .iter()
+        .filter(|command| command.starts_with("LOGIN"))
+        .count();
+    assert_eq!(logins, 2);
+}
+
+#[test]
+fn skips_malformed_messages() {
+    let server = FakeImap::start();
+    server.deliver_after(
+        1,
+        text_message(
+            "noreply@example.com",
+            "Code",
+            "Your verification code is 924671.",
+        ),
+    );
+    // 比有效邮件更新的损坏邮件
+    server.deliver_after(1, b"\x00\xff\xfe not a mail at all".to_vec());
+    server.deliver_after(
+        1,
+        "From: broken@example.com\r\nContent-Type: multipart/alternative; boundary=\"x\"\r\n\r\n--x\r\nContent-Type: text/plain; charset=\"unknown-8bit\"\r\nContent-Transfer-Encoding: base64\r\n\r\n%%%%\r\n",
+    );
+
+    assert_code(&watch_code(&server, &[]), "924671");
+}
+
+#[test]
+fn marks_matched_message_seen_by_default() {
+    let server = FakeImap::start();
+    let other = server.add_message(text_message(
+        "news@example.com",
+        "Newsletter",
+        "Nothing to see here.",
+    ));
+    server.deliver_after(
+        1,
+        text_message(
+            "noreply@example.com",
+            "Code",
+            "Your verification code is 583920.",
+        ),
+    );
+
+    assert_code(&watch_code(&server, &["--since", "1h"]), "583920");
+    assert_eq!(server.is_seen(other + 1), Some(true));
+    // 其他邮件使用 BODY.PEEK[] 读取，保持未读
+    assert_eq!(server.is_seen(other), Some(false));
+}
+
+#[test]
+fn keep_unseen_leaves_message_unread() {
+    let server = FakeImap::start();
+    server.deliver_after(
+        1,
+        text_message(
+            "noreply@example.com",
+            "Code",
+            "Your verification code is 271845.",
+        ),
+    );
+
+    assert_code(&watch_code(&server, &["--keep-unseen"]), "271845");
+    assert_eq!(server.is_seen(1), Some(false));
+}
+
+#[test]
+fn mark_seen_flag_is_accepted() {
+    let server = FakeImap::start();
+    server.deliver_after(
+        1,
+        text_message(
+            "noreply@example.com",
+            "Code",
+            "Your verification code is 271845.",
+        ),
+    );
+
+    assert_code(&watch_code(&server, &["--mark-seen"]), "271845");
+    assert_eq!(server.is_seen(1), Some(true));
+}
+
+#[test]
+fn low_confidence_match_is_left_in_place() {
+    let server = FakeImap::start();
+    server.deliver_after(
+        1,
+        text_message(
+            "shop@example.com",
+            "Hello",
+            "We shipped item 583920 yesterday, see you.",
+        ),
+    );
+
+    let output = watch_code(&server, &["--delete"]);
+    assert_code(&output, "583920");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Low confidence"));
+    // 需要用户核对的邮件既不删除也不标记为已读
+    assert_eq!(server.is_seen(1), Some(false));
+}
+
+#[test]
+fn delete_removes_matched_message() {
+    let server = FakeImap::start();
+    server.deliver_after(
+        1,
+        text_message(
+            "noreply@example.com",
+            "Code",
+            "Your verification code is 482913.",
+        ),
+    );
+
+    assert_code(&watch_code(&server, &["--delete"]), "482913");
+    assert!(!server.contains(1));
+}
+
+#[test]
+fn move_to_moves_matched_message() {
+    let server = FakeImap::start();
+    server.deliver_after(
+        1,
+        text_message(
+            "noreply@example.com",
+            "Code",
+            "Your verification code is 482913.",
+        ),
+    );
+
+    assert_code(&watch_code(&server, &["--move-to", "Archive"]), "482913");
+    assert!(!server.contains(1));
+    assert!(
+        server
+            .commands()
+            .contains(&"UID MOVE 1 \"Archive\"".to_string())
+    );
+}
+
+#[test]
+fn unknown_folder_fails_without_retrying() {
+    let server = FakeImap::start();
+
+    let output = watch_code(&server, &["--folder", "Missing"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("Folder Missing does not exist"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn watches_non_ascii_folder() {
+    let server = FakeImap::start();
+    // “垃圾邮件”的修改版 UTF-7 编码
+    server.add_folder("&V4NXPpCuTvY-");
+    server.deliver_to(
+        "&V4NXPpCuTvY-",
+        1,
+        text_message(
+            "noreply@example.com",
+            "Code",
+            "Your verification code is 530174.",
+        ),
+    );
+
+    assert_code(&watch_code(&server, &["--folder", "垃圾邮件"]), "530174");
+    assert!(
+        server
+            .commands()
+            .contains(&"SELECT \"&V4NXPpCuTvY-\"".to_string()),
+        "{:?}",
+        server.commands()
+    );
+    assert_eq!(server.is_seen(1), Some(true));
+}
+
+#[test]
+fn watches_multiple_folders() {
+    let server = FakeImap::start();
+    server.add_folder("Spam");
+    server.deliver_after(
+        1,
+        text_message("news@example.com", "Newsletter", "Nothing to see here."),
+    );
+    // 每轮依次搜索 INBOX 和 Spam，第二轮 Spam 中才出现验证码
+    server.deliver_to(
+        "Spam",
+        2,
+        text_message(
+            "noreply@example.com",
+            "Code",
+            "Your verification code is 864209.",
+        ),
+    );
+
+    let output = watch_code(&server, &["--folder", "INBOX", "--folder", "Spam"]);
+    assert_code(&output, "864209");
+
+    let commands = server.commands();
+    assert!(commands.contains(&"SELECT \"INBOX\"".to_string()));
+    assert!(commands.contains(&"SELECT \"Spam\"".to_string()));
+    // 多个文件夹时轮询，不使用 IDLE
+    assert!(!commands.iter().any(|command| command == "IDLE"));
+    assert_eq!(server.is_seen(1), Some(false));
+    assert_eq!(server.is_seen(2), Some(true));
+}
+
+#[test]
+fn folders_lists_decoded_names() {
+    let server = FakeImap::start();
+    server.add_folder("&V4NXPpCuTvY-");
+    let home = temp_home(&server.smtp_config());
+
+    let output = run_cfmail(home.path(), &["folders"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("垃圾邮件"), "{}", stdout);
+    assert!(!stdout.contains("&V4NXPpCuTvY-"), "{}", stdout);
+}
+
+#[test]
+fn idle_notification_finds_code_without_polling() {
+    let server = FakeImap::start();
+    server.deliver_during_idle(text_message(
+        "noreply@example.com",
+        "Code",
+        "Your verification code is 640285.",
+    ));
+
+    // 轮询间隔长于超时时间，只有 IDLE 推送才能在超时前找到验证码
+    let home = temp_home(&server.smtp_config());
+    let started = Instant::now();
+    let output = run_cfmail(
+        home.path(),
+        &["watch-code", "--poll-interval", "60", "--timeout", "20"],
+    );
+    assert_code(&output, "640285");
+    assert!(started.elapsed() < Duration::from_secs(10));
+
+    let commands = server.commands();
+    assert!(commands.contains(&"IDLE".to_string()), "{:?}", commands);
+    let searches = commands
+        .iter()
+        .filter(|command| command.starts_with("UID SEARCH"))
+        .count();
+    assert_eq!(searches, 2);
+}
+
+#[test]
+fn polls_when_idle_unsupported() {
+    let server = FakeImap::start_without_idle();
+    server.deliver_after(
+        1,
+        text_message(
+            "noreply@example.com",
+            "Code",
+            "Your verification code is 590314.",
+        ),
+    );
+
+    let output = watch_code(&server, &[]);
+    assert_code(&output, "590314");
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("falling back to polling"),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    assert!(!server.commands().contains(&"IDLE".to_string()));
+}
+
+#[test]
+fn starttls_upgrades_before_login() {
+    let server = FakeImap::start_starttls();
+    server.deliver_during_idle(text_message(
+        "noreply@example.com",
+        "Code",
+        "Your verification code is 815062.",
+    ));
+
+    assert_code(&watch_code(&server, &[]), "815062");
+
+    let commands = server.commands();
+    let starttls = commands.iter().position(|command| command == "STARTTLS");
+    let login = commands
+        .iter()
+        .position(|command| command.starts_with("LOGIN"));
+    assert!(
+        matches!((starttls, login), (Some(starttls), Some(login)) if starttls < login),
+        "{:?}",
+        commands
+    );
+    assert!(commands.contains(&"IDLE".to_string()), "{:?}", commands);
+}
+
+#[test]
+fn delete_runs_even_if_marking_seen_fails() {
+    let server = FakeImap::start();
+    server.reject_seen();
+    server.deliver_after(
+        1,
+        text_message(
+            "noreply@example.com",
+            "Code",
+            "Your verification code is 482913.",
+        ),
+    );
+
+    let output = watch_code(&server, &["--delete"]);
+    assert_code(&output, "482913");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Failed to mark the matched message"),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(!server.contains(1), "{:?}", server.commands());
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::imap::{FakeImap, html_message, text_message};
+use common::{run_cfmail, temp_home};
+use std::process::Output;
+
+/// 对测试服务器运行 watch-link
+fn watch_link(server: &FakeImap, extra_args: &[&str]) -> Output {
+    let home = temp_home(&server.smtp_config());
+    let mut args = vec!["watch-link", "--poll-interval", "1", "--timeout", "15"];
+    args.extend_from_slice(extra_args);
+    run_cfmail(home.path(), &args)
+}
+
+/// 断言找到了指定的链接
+fn assert_link(output: &Output, url: &str) {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success() && stdout.contains(url),
+        "expected link {}:\n{}\n{}",
+        url,
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn finds_verification_link_in_html() {
+    let server = FakeImap::start();
+    server.deliver_after(
+        1,
+        html_message(
+            "Service <noreply@service.example>",
+            "Confirm your email",
+            r#"<p><a href="https://service.example/unsubscribe">Unsubscribe</a></p>
+<p><a href="https://service.example/confirm?token=a1&amp;u=2">Confirm email</a></p>
+<img src="https://cdn.service.example/logo.png">"#,
+        ),
+    );
+    // 更新的邮件中没有验证链接，检查后被跳过
+    server.deliver_after(
+        1,
+        html_message(
+            "news@example.com",
+            "News",
+            r#"<a href="https://blog.example.net/post">Read more</a>"#,
+        ),
+    );
+
+    let output = watch_link(&server, &[]);
+    assert_link(&output, "https://service.example/confirm?token=a1&u=2");
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("unsubscribe"));
+    assert_eq!(server.is_seen(1), Some(true));
+    assert_eq!(server.is_seen(2), Some(false));
+}
+
+#[test]
+fn finds_bare_url_in_plain_text() {
+    let server = FakeImap::start();
+    server.deliver_after(
+        1,
+        text_message(
+            "noreply@service.example",
+            "Sign in",
+            "Use this link to sign in:\nhttps://service.example/login/magic?t=xyz.\n",
+        ),
+    );
+
+    assert_link(
+        &watch_link(&server, &["--keep-unseen"]),
+        "https://service.example/login/magic?t=xyz",
+    );
+    assert_eq!(server.is_seen(1), Some(false));
+}